@@ -19,8 +19,11 @@ pub enum AstNode {
         inner: Vec<AstNode>, // The nodes inside the capture
     },
 
-    // Capture reference used in replacement string for gsub
-    CaptureRef(usize), // %1, %2, ..., %9 (1-based index for Lua compatibility)
+    // Back-reference to an earlier capture
+    CaptureRef {
+        index: usize,  // %1, %2, ..., %9 (1-based index for Lua compatibility)
+        offset: usize, // Byte offset of the `%` in the pattern, for errors raised while matching
+    },
 
     // Quantified items
     Quantified {
@@ -0,0 +1,44 @@
+//! Byte string variants of the [`crate::lua`] functions.
+//!
+//! Lua strings are arbitrary byte sequences, these functions take patterns, subjects and
+//! replacements as `&[u8]` and return captures as sub-slices of the subject, so binary data
+//! and non UTF-8 encodings are processed exactly as Lua would.
+
+use super::{Capture, Pattern};
+use std::ops::Range;
+
+pub mod find;
+pub mod gmatch;
+pub mod gsub;
+pub mod r#match;
+
+pub use self::{
+    find::{find, find_plain},
+    gmatch::{GMatchIterator, gmatch},
+    gsub::{Replacer, gsub, gsub_cow, gsub_into, try_gsub},
+    r#match::r#match,
+};
+
+/// Turns the capture ranges reported by the engine into typed [`Capture`] values,
+/// `()` becomes [`Capture::Position`] and every other range is passed through `to_str`.
+/// As in lstrlib, every capture of a successful match is closed, so there is one value
+/// per capture and `captures[i]` belongs to `ranges[i]`.
+pub(crate) fn to_captures<S>(
+    pattern: &Pattern,
+    ranges: &[Option<Range<usize>>],
+    to_str: impl Fn(Range<usize>) -> S,
+) -> Vec<Capture<S>> {
+    ranges
+        .iter()
+        .enumerate()
+        .filter_map(|(i, maybe_range)| {
+            maybe_range.as_ref().map(|range| {
+                if pattern.position_captures[i] {
+                    Capture::Position(pattern.options.indexing.position(range.start))
+                } else {
+                    Capture::Str(to_str(range.clone()))
+                }
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,78 @@
+use super::{
+    super::{Capture, Indexing, Pattern, Result, engine::Literal},
+    to_captures,
+};
+
+/// Corresponds to Lua 5.3 `string.find` on byte strings.
+/// Returns indices (start, end) and captures. Indices and the `init` argument are 1-based as in Lua,
+/// use [`find_plain`] or [`crate::MatchOptions::indexing`] on a [`Pattern`] to pick another convention.
+pub fn find<'a>(
+    text: &'a [u8],
+    pattern: &[u8],
+    init: Option<isize>,
+    plain: bool,
+) -> Result<Option<(usize, usize, Vec<Capture<&'a [u8]>>)>> {
+    if plain {
+        find_plain(text, pattern, init, Indexing::default())
+    } else {
+        Pattern::new(pattern)?.find_bytes(text, init)
+    }
+}
+
+/// Same as [`find`] with `plain = true`, with indices and `init` following `indexing`.
+pub fn find_plain<'a>(
+    text: &'a [u8],
+    pattern: &[u8],
+    init: Option<isize>,
+    indexing: Indexing,
+) -> Result<Option<(usize, usize, Vec<Capture<&'a [u8]>>)>> {
+    let byte_len = text.len();
+    let start_byte_index = indexing.start_index(byte_len, init);
+
+    if pattern.is_empty() {
+        return Ok(Some((
+            indexing.position(start_byte_index),
+            start_byte_index,
+            vec![],
+        )));
+    }
+
+    if start_byte_index >= byte_len {
+        return Ok(None);
+    }
+
+    if let Some(zero_based_start_pos) = Literal::new(pattern).find(text, start_byte_index) {
+        let zero_based_end_pos = zero_based_start_pos + pattern.len();
+
+        Ok(Some((
+            indexing.position(zero_based_start_pos),
+            zero_based_end_pos,
+            vec![],
+        )))
+    } else {
+        Ok(None)
+    }
+}
+
+impl Pattern {
+    /// Same as [`find`] with `plain = false`, using the already compiled pattern.
+    pub fn find_bytes<'a>(
+        &self,
+        text: &'a [u8],
+        init: Option<isize>,
+    ) -> Result<Option<(usize, usize, Vec<Capture<&'a [u8]>>)>> {
+        let indexing = self.options.indexing;
+        let start_byte_index = indexing.start_index(text.len(), init);
+
+        match self.search(text, start_byte_index)? {
+            Some((match_byte_range, captures_byte_ranges)) => {
+                let start_pos = indexing.position(match_byte_range.start);
+                let end_pos = match_byte_range.end;
+                let captures = to_captures(self, &captures_byte_ranges, |range| &text[range]);
+
+                Ok(Some((start_pos, end_pos, captures)))
+            }
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use crate::{Pattern, Result};
+use std::borrow::Cow;
+
+mod iter;
+
+pub use iter::GMatchIterator;
+
+/// Corresponds to Lua 5.3 `string.gmatch` on byte strings
+pub fn gmatch<'a>(text: &'a [u8], pattern: &[u8]) -> Result<GMatchIterator<'a>> {
+    let pattern = Pattern::new(pattern)?;
+    Ok(GMatchIterator::new(text, Cow::Owned(pattern)))
+}
+
+impl Pattern {
+    /// Same as [`gmatch`], using the already compiled pattern.
+    pub fn gmatch_bytes<'a>(&'a self, text: &'a [u8]) -> GMatchIterator<'a> {
+        GMatchIterator::new(text, Cow::Borrowed(self))
+    }
+
+    /// Same as [`Pattern::gmatch_bytes`], starting at `init` like Lua 5.4 `string.gmatch(s, p, init)`.
+    /// Earlier [`crate::Dialect`]s ignore `init`, as their `string.gmatch` does.
+    pub fn gmatch_bytes_from<'a>(
+        &'a self,
+        text: &'a [u8],
+        init: Option<isize>,
+    ) -> GMatchIterator<'a> {
+        GMatchIterator::with_init(text, Cow::Borrowed(self), init)
+    }
+}
@@ -0,0 +1,80 @@
+use super::super::to_captures;
+use crate::{Capture, Pattern, Result};
+use std::borrow::Cow;
+
+pub struct GMatchIterator<'a> {
+    bytes: &'a [u8],
+    pattern: Cow<'a, Pattern>,
+    current_pos: usize,
+    last_match: Option<usize>,
+}
+
+impl<'a> GMatchIterator<'a> {
+    pub(crate) fn new(text: &'a [u8], pattern: Cow<'a, Pattern>) -> Self {
+        Self::with_init(text, pattern, None)
+    }
+
+    pub(crate) fn with_init(
+        text: &'a [u8],
+        pattern: Cow<'a, Pattern>,
+        init: Option<isize>,
+    ) -> Self {
+        let current_pos = if pattern.dialect.has_gmatch_init() {
+            pattern
+                .options
+                .indexing
+                .gmatch_start_index(text.len(), init)
+        } else {
+            0
+        };
+        GMatchIterator {
+            bytes: text,
+            pattern,
+            current_pos,
+            last_match: None,
+        }
+    }
+}
+
+impl<'a> Iterator for GMatchIterator<'a> {
+    type Item = Result<Vec<Capture<&'a [u8]>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.current_pos <= self.bytes.len() {
+            let (match_range, captures) =
+                match self.pattern.gmatch_search(self.bytes, self.current_pos) {
+                    Ok(Some(found)) => found,
+                    Ok(None) => break,
+                    Err(err) => {
+                        self.current_pos = self.bytes.len() + 1;
+                        return Some(Err(err));
+                    }
+                };
+
+            if self.pattern.dialect.skips_match_at_last_end() {
+                // Lua 5.4 rejects an empty match where the previous match ended
+                if self.last_match == Some(match_range.end) {
+                    self.current_pos = match_range.start + 1;
+                    continue;
+                }
+                self.current_pos = match_range.end;
+                self.last_match = Some(match_range.end);
+            } else if match_range.start == match_range.end {
+                self.current_pos = match_range.end + 1;
+            } else {
+                self.current_pos = match_range.end;
+            }
+
+            let result = if !captures.is_empty() {
+                to_captures(&self.pattern, &captures, |range| &self.bytes[range])
+            } else {
+                vec![Capture::Str(&self.bytes[match_range])]
+            };
+
+            return Some(Ok(result));
+        }
+
+        self.current_pos = self.bytes.len() + 1;
+        None
+    }
+}
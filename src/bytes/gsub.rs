@@ -0,0 +1,221 @@
+use super::{
+    super::{Capture, Error, Match, Pattern, ReplaceError, ReplacementValue, Result},
+    to_captures,
+};
+use std::{borrow::Cow, io};
+
+mod repl;
+
+use crate::value::append_value;
+pub use repl::Replacer;
+use repl::call_with_args;
+pub(crate) use repl::process_replacement_string;
+
+/// Corresponds to Lua 5.3 `string.gsub` on byte strings
+pub fn gsub(
+    text: &[u8],
+    pattern: &[u8],
+    repl: impl Replacer,
+    n: Option<usize>,
+) -> Result<(Vec<u8>, usize)> {
+    Pattern::new(pattern)?.gsub_bytes(text, repl, n)
+}
+
+/// Same as [`gsub`], borrowing `text` when no match is replaced
+pub fn gsub_cow<'t>(
+    text: &'t [u8],
+    pattern: &[u8],
+    repl: impl Replacer,
+    n: Option<usize>,
+) -> Result<(Cow<'t, [u8]>, usize)> {
+    Pattern::new(pattern)?.gsub_bytes_cow(text, repl, n)
+}
+
+/// Same as [`gsub`], writing the result to `dst` and returning the number of replacements.
+///
+/// Pattern and matching errors are returned as [`io::ErrorKind::InvalidInput`] wrapping
+/// an [`Error`]. On error, `dst` may have received part of the result.
+pub fn gsub_into(
+    dst: &mut impl io::Write,
+    text: &[u8],
+    pattern: &[u8],
+    repl: impl Replacer,
+    n: Option<usize>,
+) -> io::Result<usize> {
+    Pattern::new(pattern)?.gsub_bytes_into(dst, text, repl, n)
+}
+
+/// Same as [`gsub`] with a replacement function that can fail, stops at its first error
+pub fn try_gsub<T: ReplacementValue, E>(
+    text: &[u8],
+    pattern: &[u8],
+    f: impl FnMut(&[Capture<&[u8]>]) -> std::result::Result<T, E>,
+    n: Option<usize>,
+) -> std::result::Result<(Vec<u8>, usize), ReplaceError<E>> {
+    Pattern::new(pattern)?.try_gsub_bytes(text, f, n)
+}
+
+impl Pattern {
+    /// Same as [`gsub`], using the already compiled pattern.
+    pub fn gsub_bytes(
+        &self,
+        text: &[u8],
+        repl: impl Replacer,
+        n: Option<usize>,
+    ) -> Result<(Vec<u8>, usize)> {
+        let (result, replacements) = self.gsub_bytes_cow(text, repl, n)?;
+        Ok((result.into_owned(), replacements))
+    }
+
+    /// Same as [`gsub_cow`], using the already compiled pattern.
+    pub fn gsub_bytes_cow<'t>(
+        &self,
+        text: &'t [u8],
+        mut repl: impl Replacer,
+        n: Option<usize>,
+    ) -> Result<(Cow<'t, [u8]>, usize)> {
+        let mut result = Vec::new();
+        let replacements = self.gsub_with(
+            text,
+            n,
+            &mut result,
+            |m, result| repl.replace_append(m, result),
+            |_| Ok(()),
+        )?;
+
+        if replacements == 0 {
+            return Ok((Cow::Borrowed(text), 0));
+        }
+        Ok((Cow::Owned(result), replacements))
+    }
+
+    /// Same as [`gsub_into`], using the already compiled pattern.
+    pub fn gsub_bytes_into(
+        &self,
+        dst: &mut impl io::Write,
+        text: &[u8],
+        mut repl: impl Replacer,
+        n: Option<usize>,
+    ) -> io::Result<usize> {
+        let mut buffer = Vec::new();
+        let replacements = self.gsub_with(
+            text,
+            n,
+            &mut buffer,
+            |m, buffer| repl.replace_append(m, buffer).map_err(io::Error::from),
+            |buffer| {
+                dst.write_all(buffer)?;
+                buffer.clear();
+                Ok(())
+            },
+        )?;
+
+        if replacements == 0 {
+            dst.write_all(text)?;
+        }
+        Ok(replacements)
+    }
+
+    /// Same as [`try_gsub`], using the already compiled pattern.
+    pub fn try_gsub_bytes<T: ReplacementValue, E>(
+        &self,
+        text: &[u8],
+        mut f: impl FnMut(&[Capture<&[u8]>]) -> std::result::Result<T, E>,
+        n: Option<usize>,
+    ) -> std::result::Result<(Vec<u8>, usize), ReplaceError<E>> {
+        let mut result = Vec::new();
+        let replacements = self.gsub_with(
+            text,
+            n,
+            &mut result,
+            |m, result| {
+                let value = call_with_args(m, &mut f).map_err(ReplaceError::Replacement)?;
+                append_value(value.to_value(), m, result).map_err(ReplaceError::Pattern)
+            },
+            |_| Ok(()),
+        )?;
+
+        if replacements == 0 {
+            return Ok((text.to_vec(), 0));
+        }
+        Ok((result, replacements))
+    }
+
+    /// Drives the `gsub` loop at the byte level: appends unmatched bytes to `out`, calls
+    /// `replace` with each match to append its replacement, and `flush` after each match
+    /// and at the end so that streaming callers can drain `out`.
+    ///
+    /// Nothing is written when no match is replaced, the result is `text` itself then.
+    pub(crate) fn gsub_with<'a, E: From<Error>>(
+        &self,
+        text: &'a [u8],
+        n: Option<usize>,
+        out: &mut Vec<u8>,
+        mut replace: impl FnMut(&Match<'a>, &mut Vec<u8>) -> std::result::Result<(), E>,
+        mut flush: impl FnMut(&mut Vec<u8>) -> std::result::Result<(), E>,
+    ) -> std::result::Result<usize, E> {
+        let byte_len = text.len();
+
+        let mut last_pos = 0;
+        let mut last_match = None;
+        let mut replacements = 0;
+        let max_replacements = n.unwrap_or(usize::MAX);
+
+        while replacements < max_replacements {
+            match self.search(text, last_pos)? {
+                // Lua 5.4 rejects an empty match where the previous match ended
+                Some((match_range, _))
+                    if self.dialect.skips_match_at_last_end()
+                        && last_match == Some(match_range.end) =>
+                {
+                    if last_pos >= byte_len {
+                        break;
+                    }
+                    out.push(text[last_pos]);
+                    last_pos += 1;
+                }
+                Some((match_range, captures)) => {
+                    out.extend_from_slice(&text[last_pos..match_range.start]);
+
+                    let m = Match {
+                        subject: text,
+                        range: match_range.clone(),
+                        captures: to_captures(self, &captures, |range| &text[range]),
+                        capture_ranges: captures,
+                        index: replacements,
+                        dialect: self.dialect,
+                    };
+                    replace(&m, out)?;
+
+                    last_pos = match_range.end;
+                    last_match = Some(match_range.end);
+                    replacements += 1;
+
+                    if match_range.start == match_range.end
+                        && !self.dialect.skips_match_at_last_end()
+                    {
+                        if last_pos >= byte_len {
+                            break;
+                        }
+                        out.push(text[last_pos]);
+                        last_pos += 1;
+                    }
+                    flush(out)?;
+                }
+                None => break,
+            }
+
+            // `^` anchors only the first attempt, as in lstrlib
+            if self.program.anchored {
+                break;
+            }
+        }
+
+        if replacements > 0 {
+            out.extend_from_slice(&text[last_pos..]);
+            flush(out)?;
+        }
+
+        Ok(replacements)
+    }
+}
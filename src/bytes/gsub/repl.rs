@@ -0,0 +1,160 @@
+use crate::{
+    Capture, Dialect, Error, ErrorKind, Match, MatchFn, ReplacementValue, Result,
+    value::append_value,
+};
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// Produces the replacement of each match in [`gsub`](super::gsub).
+///
+/// Implemented for replacement strings (`&[u8]`, `Vec<u8>`, `&str`, ...), functions
+/// receiving the full match followed by the captures, and `HashMap`/`BTreeMap` tables
+/// keyed by the first capture or the whole match. Functions and tables produce a
+/// [`ReplacementValue`], e.g. `None` keeps the match. Wrap a function in [`MatchFn`]
+/// to receive the whole [`Match`], or implement this trait for custom lookups, which
+/// report a failure with [`Error::replacer`].
+pub trait Replacer {
+    /// Appends the replacement of `m` to `dst`
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()>;
+}
+
+impl Replacer for &[u8] {
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        process_replacement_string(self, m.as_bytes(), &m.captures, m.dialect, dst)
+    }
+}
+
+impl<const N: usize> Replacer for &[u8; N] {
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        self.as_slice().replace_append(m, dst)
+    }
+}
+
+impl Replacer for Vec<u8> {
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        self.as_slice().replace_append(m, dst)
+    }
+}
+
+impl Replacer for &str {
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        self.as_bytes().replace_append(m, dst)
+    }
+}
+
+/// Calls a replacement function with the full match followed by the captures
+pub(super) fn call_with_args<R>(m: &Match<'_>, f: impl FnOnce(&[Capture<&[u8]>]) -> R) -> R {
+    let mut args = Vec::with_capacity(m.captures.len() + 1);
+    args.push(Capture::Str(m.as_bytes()));
+    args.extend_from_slice(&m.captures);
+    f(&args)
+}
+
+impl<F, T> Replacer for F
+where
+    F: FnMut(&[Capture<&[u8]>]) -> T,
+    T: ReplacementValue,
+{
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        let value = call_with_args(m, self);
+        append_value(value.to_value(), m, dst)
+    }
+}
+
+impl<F, T> Replacer for MatchFn<F>
+where
+    F: FnMut(&Match<'_>) -> T,
+    T: ReplacementValue,
+{
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        append_value((self.0)(m).to_value(), m, dst)
+    }
+}
+
+/// Looks up the first capture (or the whole match) with `get`, a missing key keeps the match
+fn replace_from_table<V: ReplacementValue>(
+    m: &Match<'_>,
+    dst: &mut Vec<u8>,
+    get: impl FnOnce(&[u8]) -> Option<V>,
+) -> Result<()> {
+    let mut key = Vec::new();
+    m.first_capture().write_to(&mut key);
+    append_value(get(&key).to_value(), m, dst)
+}
+
+impl<K, V, S> Replacer for &HashMap<K, V, S>
+where
+    K: Borrow<[u8]> + Eq + Hash,
+    V: ReplacementValue,
+    S: BuildHasher,
+{
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        replace_from_table(m, dst, |key| self.get(key))
+    }
+}
+
+impl<K, V, S> Replacer for HashMap<K, V, S>
+where
+    K: Borrow<[u8]> + Eq + Hash,
+    V: ReplacementValue,
+    S: BuildHasher,
+{
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        (&*self).replace_append(m, dst)
+    }
+}
+
+impl<K: Borrow<[u8]> + Ord, V: ReplacementValue> Replacer for &BTreeMap<K, V> {
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        replace_from_table(m, dst, |key| self.get(key))
+    }
+}
+
+impl<K: Borrow<[u8]> + Ord, V: ReplacementValue> Replacer for BTreeMap<K, V> {
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        (&*self).replace_append(m, dst)
+    }
+}
+
+/// Expands `repl` like lstrlib's `add_s` and appends the result to `out`: `%0` is the whole
+/// match, `%1`..`%9` are captures (`%1` is the whole match when the pattern has none)
+/// and `%%` is a single `%`
+pub(crate) fn process_replacement_string<S: AsRef<[u8]>>(
+    repl: &[u8],
+    full_match: &[u8],
+    captures: &[Capture<S>],
+    dialect: Dialect,
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    let mut i = 0;
+    while i < repl.len() {
+        if repl[i] != b'%' {
+            out.push(repl[i]);
+            i += 1;
+            continue;
+        }
+
+        // Lua sees the string terminator after a trailing `%`
+        let escaped = repl.get(i + 1).copied().unwrap_or(0);
+        match escaped {
+            b'0' => out.extend_from_slice(full_match),
+            b'1'..=b'9' => {
+                let n = (escaped - b'0') as usize;
+                match captures.get(n - 1) {
+                    Some(capture) => capture.write_to(out),
+                    None if n == 1 && captures.is_empty() => out.extend_from_slice(full_match),
+                    None => return Err(Error::at(ErrorKind::InvalidCaptureIndex(n), i)),
+                }
+            }
+            b'%' => out.push(b'%'),
+            _ if dialect.copies_invalid_replacement_escape() => out.push(escaped),
+            _ => return Err(Error::at(ErrorKind::InvalidReplacementEscape(escaped), i)),
+        }
+        i += 2;
+    }
+
+    Ok(())
+}
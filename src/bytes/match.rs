@@ -0,0 +1,37 @@
+use super::{
+    super::{Capture, Pattern, Result},
+    to_captures,
+};
+
+/// Corresponds to Lua 5.3 `string.match` on byte strings
+pub fn r#match<'a>(
+    text: &'a [u8],
+    pattern: &[u8],
+    init: Option<isize>,
+) -> Result<Option<Vec<Capture<&'a [u8]>>>> {
+    Pattern::new(pattern)?.match_bytes(text, init)
+}
+
+impl Pattern {
+    /// Same as [`r#match`](fn@crate::bytes::match), using the already compiled pattern.
+    pub fn match_bytes<'a>(
+        &self,
+        text: &'a [u8],
+        init: Option<isize>,
+    ) -> Result<Option<Vec<Capture<&'a [u8]>>>> {
+        let start_byte_index = self.options.indexing.start_index(text.len(), init);
+
+        match self.search(text, start_byte_index)? {
+            Some((match_byte_range, captures_byte_ranges)) => {
+                let captures = to_captures(self, &captures_byte_ranges, |range| &text[range]);
+
+                if !captures.is_empty() {
+                    Ok(Some(captures))
+                } else {
+                    Ok(Some(vec![Capture::Str(&text[match_byte_range])]))
+                }
+            }
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::Dialect;
+use std::{
+    borrow::Cow,
+    fmt,
+    ops::{Deref, Range},
+};
+
+/// A single value produced by a pattern capture.
+///
+/// Lua returns captured substrings as strings and position captures `()` as numbers,
+/// this enum keeps the same distinction.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Capture<S = String> {
+    /// Captured substring, e.g. `(%a+)`
+    Str(S),
+    /// Position capture `()`, 1-based or 0-based (see [`crate::Indexing`])
+    Position(usize),
+}
+
+impl<S> Capture<S> {
+    pub fn is_position(&self) -> bool {
+        matches!(self, Capture::Position(_))
+    }
+
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            Capture::Position(pos) => Some(*pos),
+            Capture::Str(_) => None,
+        }
+    }
+
+    /// Converts the captured substring, positions are kept as they are
+    pub fn map<T>(self, f: impl FnOnce(S) -> T) -> Capture<T> {
+        match self {
+            Capture::Str(s) => Capture::Str(f(s)),
+            Capture::Position(pos) => Capture::Position(pos),
+        }
+    }
+}
+
+impl<S: Deref> Capture<S> {
+    pub fn as_deref(&self) -> Capture<&S::Target> {
+        match self {
+            Capture::Str(s) => Capture::Str(s.deref()),
+            Capture::Position(pos) => Capture::Position(*pos),
+        }
+    }
+}
+
+impl<S: AsRef<str>> Capture<S> {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Capture::Str(s) => Some(s.as_ref()),
+            Capture::Position(_) => None,
+        }
+    }
+}
+
+impl<S: AsRef<[u8]>> Capture<S> {
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Capture::Str(s) => Some(s.as_ref()),
+            Capture::Position(_) => None,
+        }
+    }
+
+    /// Appends the capture to `buf` the way Lua's `tostring` would
+    pub fn write_to(&self, buf: &mut Vec<u8>) {
+        match self {
+            Capture::Str(s) => buf.extend_from_slice(s.as_ref()),
+            Capture::Position(pos) => buf.extend_from_slice(pos.to_string().as_bytes()),
+        }
+    }
+}
+
+impl<S: fmt::Display> fmt::Display for Capture<S> {
+    /// Formats the capture the way Lua's `tostring` would
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capture::Str(s) => write!(f, "{}", s),
+            Capture::Position(pos) => write!(f, "{}", pos),
+        }
+    }
+}
+
+/// A match found by `gsub`, handed to a replacer to produce its replacement.
+///
+/// Ranges are byte offsets into the subject. With the `lua` functions a match
+/// may still split a multibyte character.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'t> {
+    pub(crate) subject: &'t [u8],
+    pub(crate) range: Range<usize>,
+    pub(crate) capture_ranges: Vec<Option<Range<usize>>>,
+    pub(crate) captures: Vec<Capture<&'t [u8]>>,
+    pub(crate) index: usize,
+    pub(crate) dialect: Dialect,
+}
+
+impl<'t> Match<'t> {
+    /// The whole matched substring
+    pub fn as_bytes(&self) -> &'t [u8] {
+        &self.subject[self.range.clone()]
+    }
+
+    /// The whole matched substring, invalid UTF-8 sequences replaced
+    /// as in [`String::from_utf8_lossy`]
+    pub fn as_str(&self) -> Cow<'t, str> {
+        String::from_utf8_lossy(self.as_bytes())
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    pub fn start(&self) -> usize {
+        self.range.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.range.end
+    }
+
+    /// Captures of the match, empty if the pattern has none.
+    /// `captures()[i]` belongs to `capture_ranges()[i]`.
+    pub fn captures(&self) -> &[Capture<&'t [u8]>] {
+        &self.captures
+    }
+
+    /// Byte range of each capture, a position capture `()` has an empty range.
+    /// Every capture takes part in a Lua pattern match, so the ranges are always `Some`.
+    pub fn capture_ranges(&self) -> &[Option<Range<usize>>] {
+        &self.capture_ranges
+    }
+
+    /// Number of matches replaced before this one
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The whole text `gsub` is working on
+    pub fn subject(&self) -> &'t [u8] {
+        self.subject
+    }
+
+    /// Lua version of the pattern, which decides how replacement strings are expanded
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// The value Lua uses for table lookups: the first capture, or the whole match
+    pub(crate) fn first_capture(&self) -> Capture<&'t [u8]> {
+        self.captures
+            .first()
+            .cloned()
+            .unwrap_or(Capture::Str(self.as_bytes()))
+    }
+}
+
+/// Wraps a function taking the whole [`Match`] to use it as a `gsub` replacer,
+/// e.g. to act on the match position or the surrounding text
+pub struct MatchFn<F>(pub F);
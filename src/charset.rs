@@ -1,4 +1,4 @@
-use super::{Error, Result};
+use super::{Dialect, Error, ErrorKind, Result};
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct CharSet {
@@ -26,19 +26,31 @@ impl CharSet {
         self.bytes[b as usize] = true;
     }
 
-    pub fn add_range(&mut self, start: u8, end: u8) -> Result<()> {
-        if start > end {
-            return Err(Error::Parser(
-                "invalid range in character class".to_string(),
-            ));
-        }
+    /// Adds `start..=end`, which is empty when `start > end` (as `z-a` in lstrlib)
+    pub fn add_range(&mut self, start: u8, end: u8) {
         for b in start..=end {
             self.add_byte(b);
         }
-        Ok(())
     }
 
-    pub fn add_class(&mut self, class_byte: u8) -> Result<()> {
+    /// Adds the members of `%class_byte`, fails if it is not a class in `dialect`
+    pub fn add_class(&mut self, class_byte: u8, dialect: Dialect) -> Result<()> {
+        if !dialect.is_class(class_byte) {
+            return Err(Error::new(ErrorKind::InvalidClass(class_byte)));
+        }
+        if class_byte.is_ascii_uppercase() {
+            // %A, %D, ... add the complement of the lowercase class
+            let mut class = CharSet::new();
+            class.add_class(class_byte.to_ascii_lowercase(), dialect)?;
+            class.invert();
+            for b in 0..=255u8 {
+                if class.contains(b) {
+                    self.add_byte(b);
+                }
+            }
+            return Ok(());
+        }
+
         match class_byte {
             b'a' => {
                 for b in b'a'..=b'z' {
@@ -59,6 +71,7 @@ impl CharSet {
                     self.add_byte(b);
                 }
             }
+            b'z' => self.add_byte(0),
             b'g' => {
                 for b in 0x21..=0x7e {
                     self.add_byte(b);
@@ -111,12 +124,7 @@ impl CharSet {
                     self.add_byte(b);
                 }
             }
-            _ => {
-                return Err(Error::Parser(format!(
-                    "invalid byte class '%{:?}'",
-                    class_byte
-                )));
-            }
+            _ => unreachable!("every class letter of a dialect is handled above"),
         }
         Ok(())
     }
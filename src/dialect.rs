@@ -0,0 +1,43 @@
+/// Lua version whose pattern matching behavior is reproduced, see [`crate::Pattern::with_dialect`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum Dialect {
+    /// `%z` matches `\0`, `%g` is not a class
+    Lua51,
+    /// Adds `%g`, keeps the deprecated `%z`
+    Lua52,
+    /// Still keeps the deprecated `%z`
+    #[default]
+    Lua53,
+    /// `%z` is no longer a class, `gsub` and `gmatch` skip an empty match right at the end
+    /// of the previous match, `gmatch` takes an `init` argument
+    Lua54,
+}
+
+impl Dialect {
+    /// Whether `%c` (or its uppercase complement) is a character class in this version
+    pub fn is_class(self, c: u8) -> bool {
+        match c.to_ascii_lowercase() {
+            b'a' | b'c' | b'd' | b'l' | b'p' | b's' | b'u' | b'w' | b'x' => true,
+            b'g' => self >= Dialect::Lua52,
+            b'z' => self <= Dialect::Lua53,
+            _ => false,
+        }
+    }
+
+    /// Whether `gsub` and `gmatch` reject an empty match ending where the previous one ended
+    /// (5.4), instead of accepting it and then stepping over one byte after every empty match
+    pub(crate) fn skips_match_at_last_end(self) -> bool {
+        self >= Dialect::Lua54
+    }
+
+    /// Whether `%x` with an invalid `x` in a `gsub` replacement string stands for `x`
+    /// (5.1) instead of raising an error
+    pub(crate) fn copies_invalid_replacement_escape(self) -> bool {
+        self == Dialect::Lua51
+    }
+
+    /// Whether `gmatch` honors its `init` argument
+    pub(crate) fn has_gmatch_init(self) -> bool {
+        self >= Dialect::Lua54
+    }
+}
@@ -1,264 +1,290 @@
 use std::ops::Range;
 
-use super::{
-    Result,
-    ast::{AstNode, Quantifier},
-};
-use state::{MAX_RECURSION_DEPTH, State};
+use super::{Error, ErrorKind, LUA_MAXCAPTURES, MatchOptions, Result, ast::AstNode};
+use program::Inst;
+use state::{Backtrack, State};
 
+pub(crate) use literal::Literal;
+pub(crate) use program::Program;
+
+mod literal;
+mod prefilter;
+mod program;
 mod state;
+mod suffix;
 
 /// Tries to find the first match of the pattern in the input string,
 /// starting the search at `start_index` (0-based).
 /// Returns the range of the full match and the ranges of captures if successful.
+///
+/// # Panics
+///
+/// If a [`AstNode::Quantified`] wraps anything but a single byte item, as lstrlib
+/// patterns cannot express that and the [`crate::Parser`] never produces it.
 pub fn find_first_match(
     pattern_ast: &[AstNode],
     input: &[u8],
     start_index: usize,
 ) -> Result<Option<(Range<usize>, Vec<Option<Range<usize>>>)>> {
-    let input_len = input.len();
+    let options = MatchOptions::default();
+    let result = search(&Program::compile(pattern_ast), &options, input, start_index)?;
+    Ok(result.map(|(range, mut captures)| {
+        captures.resize(LUA_MAXCAPTURES, None);
+        (range, captures)
+    }))
+}
 
-    if start_index > input_len {
-        // Lua allows start > len for matching empty patterns at the end
-        // Let the loop handle this. If start_index is way too large, it won't loop.
+/// Same as [`find_first_match`] for a compiled pattern, except that only the pattern's
+/// own captures are returned.
+pub(crate) fn search(
+    program: &Program,
+    options: &MatchOptions,
+    input: &[u8],
+    start_index: usize,
+) -> Result<Option<(Range<usize>, Vec<Option<Range<usize>>>)>> {
+    options.check_interrupt()?;
+
+    if let Some(literal) = &program.literal {
+        return Ok(literal
+            .find(input, start_index)
+            .map(|start| (start..start + literal.len(), Vec::new())));
     }
 
-    for i in start_index..=input_len {
-        let initial_state = State::new(input, i);
+    let mut state = State::new(input, program, options);
 
-        if let Some(final_state) = match_recursive(pattern_ast, initial_state) {
-            let full_match_range = i..final_state.current_pos;
-            return Ok(Some((full_match_range, final_state.captures)));
+    if program.anchored {
+        if start_index <= input.len() && try_at(program, &mut state, start_index)? {
+            return Ok(Some((start_index..state.current_pos, state.captures)));
         }
+        return Ok(None);
+    }
 
-        if let Some(AstNode::AnchorStart) = pattern_ast.first() {
-            if i == start_index {
-                break;
-            }
+    let mut i = start_index;
+    if let Some(suffix) = &program.suffix {
+        // Matches must end at `input.len()`, which bounds how early they can start
+        match suffix.lowest_start(input) {
+            Some(lowest_start) => i = i.max(lowest_start),
+            None => return Ok(None),
         }
-        if pattern_ast.len() == 1 {
-            if let Some(AstNode::AnchorEnd) = pattern_ast.first() {
-                if i < input_len {
-                    continue;
-                }
-            }
+    }
+
+    while let Some(candidate) = next_candidate(program, input, i) {
+        if try_at(program, &mut state, candidate)? {
+            return Ok(Some((candidate..state.current_pos, state.captures)));
         }
+        i = candidate + 1;
     }
 
     Ok(None)
 }
 
-fn match_recursive(ast: &[AstNode], mut state: State) -> Option<State> {
-    if state.recursion_depth > MAX_RECURSION_DEPTH {
+fn try_at(program: &Program, state: &mut State, start: usize) -> Result<bool> {
+    state.reset(start);
+    state.step()?;
+    run(program, state)
+}
+
+/// Returns the first position from `from` on where a match can start,
+/// skipping bytes that cannot begin one according to the pattern analysis.
+fn next_candidate(program: &Program, input: &[u8], from: usize) -> Option<usize> {
+    if from > input.len() {
         return None;
     }
-    state.recursion_depth += 1;
 
-    if ast.is_empty() {
-        return Some(state);
+    match &program.first_bytes {
+        Some(first_bytes) => input[from..]
+            .iter()
+            .position(|&b| first_bytes.contains(b))
+            .map(|offset| from + offset),
+        None => Some(from),
     }
+}
 
-    let node = ast.first().unwrap();
-    let remaining_ast = ast.get(1..).unwrap_or(&[]);
+/// Runs the program from the state's start position.
+///
+/// Backtracking uses an explicit stack of choice points and undo records instead of
+/// native recursion, so the depth of a match is only limited by available memory.
+/// Choice points are pushed in Lua's preference order, which keeps lstrlib's
+/// first-match semantics.
+fn run(program: &Program, state: &mut State) -> Result<bool> {
+    let input = state.input;
+    let input_len = input.len();
+    let mut pc = 0;
 
-    let result = match node {
-        AstNode::Literal(b) => {
-            if state.current_byte() == Some(*b) {
-                state.current_pos += 1;
-                match_recursive(remaining_ast, state)
-            } else {
-                None
-            }
-        }
-        AstNode::Any => {
-            if state.current_byte().is_some() {
-                state.current_pos += 1;
-                match_recursive(remaining_ast, state)
-            } else {
-                None
-            }
-        }
-        AstNode::Class(c, negated) => {
-            if state.check_class(*c, *negated) {
-                state.current_pos += 1;
-                match_recursive(remaining_ast, state)
-            } else {
-                None
-            }
-        }
-        AstNode::Set(charset) => {
-            if let Some(b) = state.current_byte() {
-                if charset.contains(b) {
+    loop {
+        let pos = state.current_pos;
+        let matched = match &program.insts[pc] {
+            Inst::Single(item) => {
+                let matched = state.current_byte().is_some_and(|b| item.matches(b));
+                if matched {
                     state.current_pos += 1;
-                    match_recursive(remaining_ast, state)
-                } else {
-                    None
                 }
-            } else {
-                None
-            }
-        }
-        AstNode::AnchorStart => {
-            if state.current_pos == state.search_start_pos {
-                match_recursive(remaining_ast, state)
-            } else {
-                None
+                matched
             }
-        }
-        AstNode::AnchorEnd => {
-            if state.current_pos == state.input.len() {
-                match_recursive(remaining_ast, state)
-            } else {
-                None
+            Inst::AnchorStart => pos == state.search_start_pos,
+            Inst::AnchorEnd => pos == input_len,
+            Inst::Balanced(b1, b2) => match balanced_end(input, pos, *b1, *b2) {
+                Some(end) => {
+                    state.current_pos = end;
+                    true
+                }
+                None => false,
+            },
+            Inst::Frontier(charset) => {
+                // Like Lua, the subject is treated as surrounded by '\0' bytes
+                !charset.contains(state.previous_byte().unwrap_or(b'\0'))
+                    && charset.contains(state.current_byte().unwrap_or(b'\0'))
             }
-        }
-        AstNode::Capture { index, inner } => {
-            let start_pos = state.current_pos;
-            let capture_index = *index - 1; // 0-based for [`Vec`] index
-
-            if let Some(mut success_state) = match_recursive(inner, state.clone()) {
-                let capture_range = start_pos..success_state.current_pos;
-                success_state.captures[capture_index] = Some(capture_range.clone());
-
-                if let Some(final_state) = match_recursive(remaining_ast, success_state) {
-                    return Some(final_state);
+            Inst::CaptureRef {
+                index,
+                offset,
+                position,
+            } => {
+                // Like lstrlib, a reference is only checked once matching reaches it
+                let captured = index
+                    .checked_sub(1)
+                    .and_then(|i| state.captures.get(i).cloned().flatten());
+                let Some(captured) = captured else {
+                    return Err(Error::at(ErrorKind::InvalidCaptureIndex(*index), *offset));
+                };
+                let end = pos + captured.len();
+                // lstrlib reads the length of a position capture as a huge size,
+                // so a reference to one never matches
+                let matched = !*position && end <= input_len && input[captured] == input[pos..end];
+                if matched {
+                    state.current_pos = end;
                 }
+                matched
             }
-            None
-        }
-
-        AstNode::CaptureRef(_) => None,
-
-        AstNode::Balanced(b1, b2) => {
-            if state.current_byte() != Some(*b1) {
-                return None;
+            Inst::OpenCapture(index) => {
+                set_register(state, *index, pos);
+                true
             }
-
-            let mut balance = 1;
-            let mut pos = state.current_pos + 1;
-            while pos < state.input.len() {
-                if state.input[pos] == *b2 {
-                    balance -= 1;
-                    if balance == 0 {
-                        state.current_pos = pos + 1;
-                        return match_recursive(remaining_ast, state);
+            Inst::CloseCapture(index) => {
+                let value = Some(state.registers[*index]..pos);
+                let old = std::mem::replace(&mut state.captures[*index], value);
+                state.backtrack.push(Backtrack::Capture {
+                    index: *index,
+                    value: old,
+                });
+                true
+            }
+            Inst::MaxExpand { item, min } => {
+                let count = input[pos..]
+                    .iter()
+                    .take_while(|&&b| item.matches(b))
+                    .count();
+                let matched = count >= *min;
+                if matched {
+                    if count > *min {
+                        state.backtrack.push(Backtrack::MaxExpand {
+                            pc,
+                            start: pos,
+                            count,
+                            min: *min,
+                        });
                     }
-                } else if state.input[pos] == *b1 {
-                    balance += 1;
+                    state.current_pos = pos + count;
                 }
-                pos += 1;
+                matched
             }
-            None
-        }
-
-        AstNode::Frontier(charset) => {
-            let prev_byte_in_set = state.previous_byte().is_some_and(|b| charset.contains(b));
-            let next_byte_in_set = state.current_byte().is_some_and(|b| charset.contains(b));
-
-            if !prev_byte_in_set && next_byte_in_set {
-                match_recursive(remaining_ast, state)
-            } else {
-                None
+            Inst::MinExpand(_) => {
+                state.backtrack.push(Backtrack::MinExpand { pc, pos });
+                true
             }
-        }
-
-        AstNode::Quantified { item, quantifier } => {
-            match quantifier {
-                Quantifier::Star | Quantifier::Plus => {
-                    // Greedy *, +
-                    let min_matches = if *quantifier == Quantifier::Plus {
-                        1
-                    } else {
-                        0
-                    };
-                    match_greedy_quantifier(item.as_ref(), remaining_ast, state, min_matches)
-                }
-                Quantifier::Question => {
-                    // Greedy ? (0 or 1)
-                    let item_ast = std::slice::from_ref(item.as_ref());
-                    if let Some(state_after_1) = match_recursive(item_ast, state.clone()) {
-                        if let Some(final_state) =
-                            match_recursive(remaining_ast, state_after_1.clone())
-                        {
-                            return Some(final_state.clone());
-                        }
-                    }
-                    match_recursive(remaining_ast, state)
-                }
-                Quantifier::Minus => {
-                    match_non_greedy_quantifier(item.as_ref(), remaining_ast, state)
+            Inst::Optional(item) => {
+                if state.current_byte().is_some_and(|b| item.matches(b)) {
+                    state.backtrack.push(Backtrack::Resume { pc: pc + 1, pos });
+                    state.current_pos += 1;
                 }
+                true
             }
-        }
-    };
-    result
-}
-
-fn match_greedy_quantifier(
-    item: &AstNode,
-    remaining_ast: &[AstNode],
-    initial_state: State,
-    min_matches: usize,
-) -> Option<State> {
-    let mut current_state = initial_state;
-    let mut successful_match_states = Vec::new();
+            Inst::Match => return Ok(true),
+        };
 
-    for _ in 0..min_matches {
-        if let Some(next_state) = match_recursive(std::slice::from_ref(item), current_state.clone())
-        {
-            if next_state.current_pos == current_state.current_pos {
-                return None;
-            }
-            current_state = next_state;
+        if matched {
+            pc += 1;
         } else {
-            return None;
+            match backtrack(program, state)? {
+                Some(resume_pc) => pc = resume_pc,
+                None => return Ok(false),
+            }
         }
     }
-    successful_match_states.push(current_state.clone());
+}
 
-    while let Some(next_state) = match_recursive(std::slice::from_ref(item), current_state.clone())
-    {
-        if next_state.current_pos == current_state.current_pos {
-            successful_match_states.push(next_state.clone());
+/// Pops the backtracking stack until a choice point yields an alternative,
+/// returning the instruction to resume at.
+fn backtrack(program: &Program, state: &mut State) -> Result<Option<usize>> {
+    while let Some(entry) = state.backtrack.pop() {
+        if entry.is_choice_point() {
+            state.step()?;
         }
-        current_state = next_state;
-        successful_match_states.push(current_state.clone());
-    }
-
-    while !successful_match_states.is_empty() {
-        let state_to_try = successful_match_states.pop()?;
-        if let Some(final_state) = match_recursive(remaining_ast, state_to_try.clone()) {
-            return Some(final_state.clone());
+        match entry {
+            Backtrack::Capture { index, value } => state.captures[index] = value,
+            Backtrack::Register { index, value } => state.registers[index] = value,
+            Backtrack::Resume { pc, pos } => {
+                state.current_pos = pos;
+                return Ok(Some(pc));
+            }
+            Backtrack::MaxExpand {
+                pc,
+                start,
+                count,
+                min,
+            } => {
+                let count = count - 1;
+                if count > min {
+                    state.backtrack.push(Backtrack::MaxExpand {
+                        pc,
+                        start,
+                        count,
+                        min,
+                    });
+                }
+                state.current_pos = start + count;
+                return Ok(Some(pc + 1));
+            }
+            Backtrack::MinExpand { pc, pos } => {
+                let Inst::MinExpand(item) = &program.insts[pc] else {
+                    unreachable!("MinExpand entry must point at a MinExpand instruction")
+                };
+                if state.input.get(pos).is_some_and(|&b| item.matches(b)) {
+                    state
+                        .backtrack
+                        .push(Backtrack::MinExpand { pc, pos: pos + 1 });
+                    state.current_pos = pos + 1;
+                    return Ok(Some(pc + 1));
+                }
+            }
         }
     }
-
-    None
+    Ok(None)
 }
 
-fn match_non_greedy_quantifier(
-    item: &AstNode,
-    remaining_ast: &[AstNode],
-    initial_state: State,
-) -> Option<State> {
-    let mut current_state = initial_state;
+fn set_register(state: &mut State, index: usize, value: usize) {
+    let old = std::mem::replace(&mut state.registers[index], value);
+    state
+        .backtrack
+        .push(Backtrack::Register { index, value: old });
+}
 
-    loop {
-        if let Some(final_state) = match_recursive(remaining_ast, current_state.clone()) {
-            return Some(final_state.clone());
-        }
+/// Returns the position right after the `%bxy` match starting at `pos`
+fn balanced_end(input: &[u8], pos: usize, b1: u8, b2: u8) -> Option<usize> {
+    if input.get(pos) != Some(&b1) {
+        return None;
+    }
 
-        let item_ast = std::slice::from_ref(item);
-        if let Some(next_state) = match_recursive(item_ast, current_state.clone()) {
-            if next_state.current_pos == current_state.current_pos {
-                if let Some(final_state) = match_recursive(remaining_ast, next_state.clone()) {
-                    return Some(final_state.clone());
-                } else {
-                    return None;
-                }
+    let mut balance = 1;
+    for (offset, &byte) in input[pos + 1..].iter().enumerate() {
+        if byte == b2 {
+            balance -= 1;
+            if balance == 0 {
+                return Some(pos + 1 + offset + 1);
             }
-            current_state = next_state;
-        } else {
-            return None;
+        } else if byte == b1 {
+            balance += 1;
         }
     }
+    None
 }
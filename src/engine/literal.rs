@@ -0,0 +1,65 @@
+/// Substring search for patterns made only of literal bytes and for plain `find`.
+///
+/// Like memchr's `memmem` prefilter, it scans for the rarest byte of the needle
+/// (by a fixed estimate of byte frequencies in typical text) and only compares
+/// the whole needle around its occurrences.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Literal {
+    needle: Box<[u8]>,
+    rare: usize,
+}
+
+impl Literal {
+    pub fn new(needle: &[u8]) -> Self {
+        let rare = (0..needle.len())
+            .min_by_key(|&i| frequency_rank(needle[i]))
+            .unwrap_or(0);
+        Literal {
+            needle: needle.into(),
+            rare,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.needle.len()
+    }
+
+    /// Returns the start of the first occurrence of the needle at or after `from`
+    pub fn find(&self, haystack: &[u8], from: usize) -> Option<usize> {
+        let last_start = haystack.len().checked_sub(self.needle.len())?;
+        if from > last_start {
+            return None;
+        }
+        if self.needle.is_empty() {
+            return Some(from);
+        }
+
+        let rare_byte = self.needle[self.rare];
+        let scan_end = last_start + self.rare + 1;
+        let mut pos = from + self.rare;
+        while pos < scan_end {
+            let offset = haystack[pos..scan_end]
+                .iter()
+                .position(|&b| b == rare_byte)?;
+            let start = pos + offset - self.rare;
+            if haystack[start..start + self.needle.len()] == *self.needle {
+                return Some(start);
+            }
+            pos += offset + 1;
+        }
+        None
+    }
+}
+
+/// Rough estimate of how common a byte is in text, higher is more common
+fn frequency_rank(b: u8) -> u8 {
+    match b {
+        b' ' | b'e' | b't' | b'a' | b'o' | b'i' | b'n' | b's' | b'r' => 255,
+        b'a'..=b'z' => 200,
+        b'0'..=b'9' | b'.' | b',' | b'\n' | b'-' | b'_' | b'/' | b':' => 160,
+        b'A'..=b'Z' => 140,
+        b'!'..=b'~' | b'\t' | b'\r' => 100,
+        0 | 0x80..=0xff => 60,
+        _ => 20,
+    }
+}
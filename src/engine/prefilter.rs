@@ -0,0 +1,61 @@
+use crate::{
+    CharSet,
+    ast::{AstNode, Quantifier},
+};
+
+use super::program::Single;
+
+/// Computes the set of bytes a match of `ast` can start with.
+///
+/// Returns `None` when any position may start a match, i.e. the pattern can match
+/// the empty string or begins with something that is not known byte by byte
+/// (`.`, a back-reference).
+pub fn first_bytes(ast: &[AstNode]) -> Option<CharSet> {
+    let mut set = CharSet::new();
+    match add_first_bytes(ast, &mut set)? {
+        Nullable::No => Some(set),
+        Nullable::Yes => None,
+    }
+}
+
+enum Nullable {
+    Yes,
+    No,
+}
+
+/// Adds the possible first bytes of the sequence to `set` and tells whether
+/// the sequence can match without consuming anything.
+fn add_first_bytes(ast: &[AstNode], set: &mut CharSet) -> Option<Nullable> {
+    for node in ast {
+        if let Nullable::No = add_node_first_bytes(node, set)? {
+            return Some(Nullable::No);
+        }
+    }
+    Some(Nullable::Yes)
+}
+
+fn add_node_first_bytes(node: &AstNode, set: &mut CharSet) -> Option<Nullable> {
+    match node {
+        AstNode::Any | AstNode::CaptureRef { .. } => None,
+        AstNode::Literal(_) | AstNode::Class(_, _) | AstNode::Set(_) => {
+            let single = Single::from_node(node)?;
+            (0..=u8::MAX)
+                .filter(|&b| single.matches(b))
+                .for_each(|b| set.add_byte(b));
+            Some(Nullable::No)
+        }
+        AstNode::Balanced(b1, _) => {
+            set.add_byte(*b1);
+            Some(Nullable::No)
+        }
+        AstNode::AnchorStart | AstNode::AnchorEnd | AstNode::Frontier(_) => Some(Nullable::Yes),
+        AstNode::Capture { inner, .. } => add_first_bytes(inner, set),
+        AstNode::Quantified { item, quantifier } => {
+            let item_nullable = add_node_first_bytes(item, set)?;
+            match quantifier {
+                Quantifier::Plus => Some(item_nullable),
+                Quantifier::Star | Quantifier::Minus | Quantifier::Question => Some(Nullable::Yes),
+            }
+        }
+    }
+}
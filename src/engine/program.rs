@@ -0,0 +1,199 @@
+use crate::{
+    CharSet,
+    ast::{AstNode, Quantifier},
+};
+
+use super::{literal::Literal, prefilter::first_bytes, state::State, suffix::Suffix};
+
+/// A byte-consuming item: literal, `.`, class or set
+#[derive(Debug, Clone, PartialEq)]
+pub enum Single {
+    Literal(u8),
+    Any,
+    Class(u8, bool),
+    Set(Box<CharSet>),
+}
+
+impl Single {
+    pub fn from_node(node: &AstNode) -> Option<Self> {
+        match node {
+            AstNode::Literal(b) => Some(Single::Literal(*b)),
+            AstNode::Any => Some(Single::Any),
+            AstNode::Class(c, negated) => Some(Single::Class(*c, *negated)),
+            AstNode::Set(charset) => Some(Single::Set(Box::new(charset.clone()))),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn matches(&self, byte: u8) -> bool {
+        match self {
+            Single::Literal(b) => byte == *b,
+            Single::Any => true,
+            Single::Class(c, negated) => State::class_matches(byte, *c, *negated),
+            Single::Set(charset) => charset.contains(byte),
+        }
+    }
+}
+
+/// One instruction of a compiled pattern.
+///
+/// Execution falls through to the next instruction unless stated otherwise,
+/// a failing instruction makes the engine backtrack to the latest choice point.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+    Single(Single),
+    AnchorStart,
+    AnchorEnd,
+    Balanced(u8, u8),
+    Frontier(Box<CharSet>),
+    /// Back-reference `%n`, numbered as in the pattern, with the pattern offset of its `%`.
+    /// A reference to a position capture never matches, as in lstrlib.
+    CaptureRef {
+        index: usize,
+        offset: usize,
+        position: bool,
+    },
+    /// Records the start of capture `n` (0-based)
+    OpenCapture(usize),
+    /// Closes capture `n` (0-based) at the current position
+    CloseCapture(usize),
+    /// Greedy `*` (`min == 0`) or `+` (`min == 1`) over a single byte item
+    MaxExpand {
+        item: Single,
+        min: usize,
+    },
+    /// Lazy `-` over a single byte item
+    MinExpand(Single),
+    /// `?` over a single byte item
+    Optional(Single),
+    Match,
+}
+
+/// A pattern compiled for the backtracking engine.
+///
+/// Like in lstrlib, quantifiers only apply to single byte items, so the program
+/// is a straight sequence and every choice point comes from a quantifier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub insts: Vec<Inst>,
+    pub captures: usize,
+    pub anchored: bool,
+    /// Bytes a match must start with, `None` if any position can start one
+    pub first_bytes: Option<Box<CharSet>>,
+    /// Set when the pattern is only literal bytes, such patterns skip the engine
+    pub literal: Option<Literal>,
+    /// Set when the pattern ends with `$`
+    pub suffix: Option<Suffix>,
+}
+
+impl Program {
+    /// # Panics
+    ///
+    /// If a quantifier wraps anything but a single byte item, which the parser never produces.
+    pub fn compile(ast: &[AstNode]) -> Self {
+        let mut compiler = Compiler {
+            insts: Vec::new(),
+            position_captures: Vec::new(),
+        };
+        compiler.sequence(ast);
+        compiler.insts.push(Inst::Match);
+
+        Program {
+            insts: compiler.insts,
+            captures: capture_count(ast),
+            anchored: matches!(ast.first(), Some(AstNode::AnchorStart)),
+            first_bytes: first_bytes(ast).map(Box::new),
+            literal: literal_bytes(ast).map(|bytes| Literal::new(&bytes)),
+            suffix: Suffix::analyze(ast),
+        }
+    }
+}
+
+fn literal_bytes(ast: &[AstNode]) -> Option<Vec<u8>> {
+    if ast.is_empty() {
+        return None;
+    }
+    ast.iter()
+        .map(|node| match node {
+            AstNode::Literal(b) => Some(*b),
+            _ => None,
+        })
+        .collect()
+}
+
+fn capture_count(ast: &[AstNode]) -> usize {
+    ast.iter()
+        .map(|node| match node {
+            AstNode::Capture { index, inner } => (*index).max(capture_count(inner)),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+struct Compiler {
+    insts: Vec<Inst>,
+    /// Indices of the position captures `()` compiled so far, a reference can only
+    /// match captures that come before it
+    position_captures: Vec<usize>,
+}
+
+impl Compiler {
+    fn sequence(&mut self, ast: &[AstNode]) {
+        for node in ast {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &AstNode) {
+        if let Some(single) = Single::from_node(node) {
+            self.insts.push(Inst::Single(single));
+            return;
+        }
+
+        match node {
+            AstNode::AnchorStart => self.insts.push(Inst::AnchorStart),
+            AstNode::AnchorEnd => self.insts.push(Inst::AnchorEnd),
+            AstNode::Balanced(b1, b2) => self.insts.push(Inst::Balanced(*b1, *b2)),
+            AstNode::Frontier(charset) => {
+                self.insts.push(Inst::Frontier(Box::new(charset.clone())))
+            }
+            AstNode::CaptureRef { index, offset } => self.insts.push(Inst::CaptureRef {
+                index: *index,
+                offset: *offset,
+                position: self.position_captures.contains(index),
+            }),
+            AstNode::Capture { index, inner } => {
+                if inner.is_empty() {
+                    self.position_captures.push(*index);
+                }
+                self.insts.push(Inst::OpenCapture(*index - 1));
+                self.sequence(inner);
+                self.insts.push(Inst::CloseCapture(*index - 1));
+            }
+            AstNode::Quantified { item, quantifier } => self.quantified(item, *quantifier),
+            AstNode::Literal(_) | AstNode::Any | AstNode::Class(_, _) | AstNode::Set(_) => {
+                unreachable!("single byte items are handled above")
+            }
+        }
+    }
+
+    fn quantified(&mut self, item: &AstNode, quantifier: Quantifier) {
+        let Some(single) = Single::from_node(item) else {
+            panic!("a quantifier must follow a single byte item, not {item:?}");
+        };
+        self.insts.push(match quantifier {
+            Quantifier::Star => Inst::MaxExpand {
+                item: single,
+                min: 0,
+            },
+            Quantifier::Plus => Inst::MaxExpand {
+                item: single,
+                min: 1,
+            },
+            Quantifier::Minus => Inst::MinExpand(single),
+            Quantifier::Question => Inst::Optional(single),
+        });
+    }
+}
@@ -1,29 +1,85 @@
-use std::{ops::Range, rc::Rc};
+use std::ops::Range;
 
-use crate::LUA_MAXCAPTURES;
+use super::program::Program;
+use crate::{MatchOptions, Result};
 
-#[derive(Clone)]
-pub struct State {
-    pub input: Rc<[u8]>,
+/// Matching state for one search. It borrows the subject and is reset, not rebuilt,
+/// for every start position, so a search allocates its buffers only once.
+pub struct State<'a> {
+    pub input: &'a [u8],
     pub current_pos: usize,
     pub search_start_pos: usize,
     pub captures: Vec<Option<Range<usize>>>,
-    pub recursion_depth: u32,
+    pub registers: Vec<usize>,
+    pub backtrack: Vec<Backtrack>,
+    pub options: &'a MatchOptions,
+    pub steps: u64,
 }
 
-pub const MAX_RECURSION_DEPTH: u32 = 500;
+/// An entry of the backtracking stack: either a choice point to resume from
+/// or an undo record restoring what was overwritten after the last choice point.
+pub enum Backtrack {
+    Resume {
+        pc: usize,
+        pos: usize,
+    },
+    /// Gives back one byte of the [`super::program::Inst::MaxExpand`] at `pc`
+    MaxExpand {
+        pc: usize,
+        start: usize,
+        count: usize,
+        min: usize,
+    },
+    /// Takes one more byte for the [`super::program::Inst::MinExpand`] at `pc`
+    MinExpand {
+        pc: usize,
+        pos: usize,
+    },
+    Capture {
+        index: usize,
+        value: Option<Range<usize>>,
+    },
+    Register {
+        index: usize,
+        value: usize,
+    },
+}
+
+impl Backtrack {
+    pub fn is_choice_point(&self) -> bool {
+        !matches!(self, Backtrack::Capture { .. } | Backtrack::Register { .. })
+    }
+}
 
-impl State {
-    pub fn new(input_slice: &[u8], start_pos: usize) -> Self {
+impl<'a> State<'a> {
+    pub fn new(input: &'a [u8], program: &Program, options: &'a MatchOptions) -> Self {
         State {
-            input: Rc::from(input_slice),
-            current_pos: start_pos,
-            search_start_pos: start_pos,
-            captures: vec![None; LUA_MAXCAPTURES],
-            recursion_depth: 0,
+            input,
+            current_pos: 0,
+            search_start_pos: 0,
+            captures: vec![None; program.captures],
+            registers: vec![0; program.captures],
+            backtrack: Vec::new(),
+            options,
+            steps: 0,
         }
     }
 
+    /// Counts a step against the limits in [`MatchOptions`]
+    #[inline]
+    pub fn step(&mut self) -> Result<()> {
+        self.steps += 1;
+        self.options.check(self.steps)
+    }
+
+    /// Prepares the state for a match attempt at `start_pos`
+    pub fn reset(&mut self, start_pos: usize) {
+        self.current_pos = start_pos;
+        self.search_start_pos = start_pos;
+        self.captures.fill(None);
+        self.backtrack.clear();
+    }
+
     #[inline]
     pub fn current_byte(&self) -> Option<u8> {
         self.input.get(self.current_pos).copied()
@@ -39,24 +95,21 @@ impl State {
     }
 
     #[inline]
-    pub fn check_class(&self, class_byte: u8, negated: bool) -> bool {
-        if let Some(byte) = self.current_byte() {
-            let matches = match class_byte {
-                b'a' => byte.is_ascii_alphabetic(),
-                b'c' => byte.is_ascii_control(),
-                b'd' => byte.is_ascii_digit(),
-                b'g' => byte.is_ascii_graphic() && byte != b' ', // Lua's %g excludes space
-                b'l' => byte.is_ascii_lowercase(),
-                b'p' => byte.is_ascii_punctuation(),
-                b's' => byte.is_ascii_whitespace(),
-                b'u' => byte.is_ascii_uppercase(),
-                b'w' => byte.is_ascii_alphanumeric(),
-                b'x' => byte.is_ascii_hexdigit(),
-                _ => false,
-            };
-            matches ^ negated // XOR handles negation
-        } else {
-            false
-        }
+    pub fn class_matches(byte: u8, class_byte: u8, negated: bool) -> bool {
+        let matches = match class_byte {
+            b'a' => byte.is_ascii_alphabetic(),
+            b'c' => byte.is_ascii_control(),
+            b'd' => byte.is_ascii_digit(),
+            b'g' => byte.is_ascii_graphic() && byte != b' ', // Lua's %g excludes space
+            b'l' => byte.is_ascii_lowercase(),
+            b'p' => byte.is_ascii_punctuation(),
+            b's' => byte.is_ascii_whitespace(),
+            b'u' => byte.is_ascii_uppercase(),
+            b'w' => byte.is_ascii_alphanumeric(),
+            b'x' => byte.is_ascii_hexdigit(),
+            b'z' => byte == 0, // up to Lua 5.3
+            _ => false,
+        };
+        matches ^ negated // XOR handles negation
     }
 }
@@ -0,0 +1,138 @@
+use crate::ast::{AstNode, Quantifier};
+
+use super::program::Single;
+
+/// Trailing items of a pattern ending with `$`, used to tell how far from the end
+/// of the subject a match can start without trying every position from the front.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suffix {
+    /// Items before the `$`, last one first
+    items: Vec<SuffixItem>,
+    /// Longest text the rest of the pattern can match, `None` if unbounded
+    rest_max_len: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SuffixItem {
+    Single(Single),
+    /// `*`, `-` (`min == 0`) or `+` (`min == 1`) over a single byte item
+    Repeat {
+        item: Single,
+        min: usize,
+    },
+    Optional(Single),
+}
+
+impl Suffix {
+    pub fn analyze(ast: &[AstNode]) -> Option<Self> {
+        let (AstNode::AnchorEnd, rest) = ast.split_last()? else {
+            return None;
+        };
+
+        let mut atoms = Vec::new();
+        flatten(rest, &mut atoms);
+
+        let mut items = Vec::new();
+        let mut remaining = atoms.len();
+        while remaining > 0 {
+            let Some(item) = SuffixItem::from_node(atoms[remaining - 1]) else {
+                break;
+            };
+            items.push(item);
+            remaining -= 1;
+        }
+
+        let rest_max_len = atoms[..remaining]
+            .iter()
+            .try_fold(0, |len, node| Some(len + max_len(node)?));
+        Some(Suffix {
+            items,
+            rest_max_len,
+        })
+    }
+
+    /// Returns the lowest position a match can start at, or `None` when
+    /// the end of the subject rules out any match.
+    pub fn lowest_start(&self, input: &[u8]) -> Option<usize> {
+        let mut pos = input.len();
+        // Whether the items walked so far can only end exactly at `pos`
+        let mut exact = true;
+
+        for item in &self.items {
+            match item {
+                SuffixItem::Single(single) => {
+                    if exact && (pos == 0 || !single.matches(input[pos - 1])) {
+                        return None;
+                    }
+                    pos = pos.saturating_sub(1);
+                }
+                SuffixItem::Repeat { item, min } => {
+                    let run = input[..pos]
+                        .iter()
+                        .rev()
+                        .take_while(|&&b| item.matches(b))
+                        .count();
+                    if exact && run < *min {
+                        return None;
+                    }
+                    pos -= run;
+                    exact = false;
+                }
+                SuffixItem::Optional(single) => {
+                    if pos > 0 && single.matches(input[pos - 1]) {
+                        pos -= 1;
+                    }
+                    exact = false;
+                }
+            }
+        }
+
+        Some(match self.rest_max_len {
+            Some(len) => pos.saturating_sub(len),
+            None => 0,
+        })
+    }
+}
+
+impl SuffixItem {
+    fn from_node(node: &AstNode) -> Option<Self> {
+        match node {
+            AstNode::Quantified { item, quantifier } => {
+                let item = Single::from_node(item)?;
+                Some(match quantifier {
+                    Quantifier::Star | Quantifier::Minus => SuffixItem::Repeat { item, min: 0 },
+                    Quantifier::Plus => SuffixItem::Repeat { item, min: 1 },
+                    Quantifier::Question => SuffixItem::Optional(item),
+                })
+            }
+            _ => Single::from_node(node).map(SuffixItem::Single),
+        }
+    }
+}
+
+/// Replaces captures with their contents, the captured text is matched
+/// exactly as if the parentheses were not there.
+fn flatten<'a>(ast: &'a [AstNode], atoms: &mut Vec<&'a AstNode>) {
+    for node in ast {
+        match node {
+            AstNode::Capture { inner, .. } => flatten(inner, atoms),
+            _ => atoms.push(node),
+        }
+    }
+}
+
+fn max_len(node: &AstNode) -> Option<usize> {
+    match node {
+        AstNode::Literal(_) | AstNode::Any | AstNode::Class(_, _) | AstNode::Set(_) => Some(1),
+        AstNode::AnchorStart | AstNode::AnchorEnd | AstNode::Frontier(_) => Some(0),
+        AstNode::Balanced(_, _) | AstNode::CaptureRef { .. } => None,
+        AstNode::Capture { inner, .. } => inner
+            .iter()
+            .try_fold(0, |len, node| Some(len + max_len(node)?)),
+        AstNode::Quantified { item, quantifier } => match (quantifier, max_len(item)?) {
+            (Quantifier::Question, len) => Some(len),
+            (_, 0) => Some(0),
+            _ => None,
+        },
+    }
+}
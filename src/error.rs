@@ -0,0 +1,161 @@
+use std::fmt;
+
+/// What went wrong, see [`Error::kind`].
+///
+/// Kinds that lstrlib also reports are displayed with the exact Lua 5.3 message,
+/// e.g. `malformed pattern (missing ']')` for [`ErrorKind::UnfinishedClass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The pattern ends with a lone `%`
+    EndsWithPercent,
+    /// `%b` not followed by two delimiter bytes
+    MissingBalancedArguments,
+    /// `%f` not followed by `[`
+    MissingBracketAfterFrontier,
+    /// `[` without the closing `]`
+    UnfinishedClass,
+    /// A byte that is not a class of the dialect (see [`crate::CharSet::add_class`])
+    InvalidClass(u8),
+    /// `(` without the closing `)`
+    UnfinishedCapture,
+    /// `)` without an open capture
+    UnmatchedCaptureClose,
+    /// More than [`crate::LUA_MAXCAPTURES`] captures
+    TooManyCaptures,
+    /// Reference `%N` to a capture that does not exist (or is not closed yet in a pattern)
+    InvalidCaptureIndex(usize),
+    /// `%` followed by something other than a digit or `%` in a replacement string
+    InvalidReplacementEscape(u8),
+    /// A replacement function or table produced a value other than a string, a number,
+    /// `nil` or `false`, named by its Lua type
+    InvalidReplacementValue(&'static str),
+    /// A limit from [`crate::MatchOptions`] was reached
+    PatternTooComplex,
+    /// The cancel flag from [`crate::MatchOptions`] was set
+    Cancelled,
+    /// The result of a `str` function is not valid UTF-8 (see [`crate::bytes`])
+    InvalidUtf8,
+    /// The destination of `gsub_into` returned an error
+    WriteFailed,
+    /// A custom [`crate::Replacer`] failed, see [`Error::replacer`]
+    Replacer(&'static str),
+}
+
+/// Error returned by pattern compilation and matching.
+///
+/// Errors caused by the pattern itself carry the byte offset of the faulty part,
+/// e.g. the `[` of an unfinished set or the `%` of a `%b` missing its arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: Option<usize>,
+}
+
+impl Error {
+    pub(crate) const fn new(kind: ErrorKind) -> Self {
+        Error { kind, offset: None }
+    }
+
+    pub(crate) const fn at(kind: ErrorKind, offset: usize) -> Self {
+        Error {
+            kind,
+            offset: Some(offset),
+        }
+    }
+
+    /// Error for a custom [`crate::Replacer`] or [`crate::bytes::Replacer`] to return
+    /// when it cannot produce a replacement, `gsub` stops and returns it as is
+    pub const fn replacer(message: &'static str) -> Self {
+        Error::new(ErrorKind::Replacer(message))
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Byte offset in the pattern the error refers to, or in the replacement
+    /// string for errors raised while expanding it in `gsub`
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::EndsWithPercent => write!(f, "malformed pattern (ends with '%')"),
+            ErrorKind::MissingBalancedArguments => {
+                write!(f, "malformed pattern (missing arguments to '%b')")
+            }
+            ErrorKind::MissingBracketAfterFrontier => {
+                write!(f, "missing '[' after '%f' in pattern")
+            }
+            ErrorKind::UnfinishedClass => write!(f, "malformed pattern (missing ']')"),
+            ErrorKind::InvalidClass(b) => {
+                write!(f, "invalid character class '%{}'", b.escape_ascii())
+            }
+            ErrorKind::UnfinishedCapture => write!(f, "unfinished capture"),
+            ErrorKind::UnmatchedCaptureClose => write!(f, "invalid pattern capture"),
+            ErrorKind::TooManyCaptures => write!(f, "too many captures"),
+            ErrorKind::InvalidCaptureIndex(n) => write!(f, "invalid capture index %{}", n),
+            ErrorKind::InvalidReplacementEscape(_) => {
+                write!(f, "invalid use of '%' in replacement string")
+            }
+            ErrorKind::InvalidReplacementValue(type_name) => {
+                write!(f, "invalid replacement value (a {})", type_name)
+            }
+            ErrorKind::PatternTooComplex => write!(f, "pattern too complex"),
+            ErrorKind::Cancelled => write!(f, "pattern matching cancelled"),
+            ErrorKind::InvalidUtf8 => write!(f, "result is not valid UTF-8 (use bytes functions)"),
+            ErrorKind::WriteFailed => write!(f, "failed to write the result"),
+            ErrorKind::Replacer(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
+    }
+}
+
+/// Error returned by the `try_gsub` functions: a pattern or matching [`Error`],
+/// or the first error returned by the replacement function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplaceError<E> {
+    Pattern(Error),
+    Replacement(E),
+}
+
+impl<E> From<Error> for ReplaceError<E> {
+    fn from(err: Error) -> Self {
+        ReplaceError::Pattern(err)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ReplaceError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplaceError::Pattern(err) => write!(f, "{}", err),
+            ReplaceError::Replacement(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ReplaceError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReplaceError::Pattern(err) => Some(err),
+            ReplaceError::Replacement(err) => Some(err),
+        }
+    }
+}
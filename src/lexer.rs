@@ -1,58 +1,42 @@
-use super::{Error, Result};
+use super::{Dialect, Error, ErrorKind, Result};
 
 pub mod token;
 
 pub use token::Token;
 
-fn is_class_byte(c: u8) -> bool {
-    matches!(
-        c,
-        b'a' | b'c'
-            | b'd'
-            | b'g'
-            | b'l'
-            | b'p'
-            | b's'
-            | b'u'
-            | b'w'
-            | b'x'
-            | b'A'
-            | b'C'
-            | b'D'
-            | b'G'
-            | b'L'
-            | b'P'
-            | b'S'
-            | b'U'
-            | b'W'
-            | b'X'
-    )
-}
-
-fn is_escapable_magic_byte(c: u8) -> bool {
-    matches!(
-        c,
-        b'(' | b')' | b'.' | b'%' | b'[' | b']' | b'*' | b'+' | b'-' | b'?' | b'^' | b'$'
-    )
-}
-
 pub struct Lexer<'a> {
     input: &'a [u8],
     pos: usize,
     capture_depth: usize,
-    set_depth: usize,
+    in_set: bool,
+    set_start: usize, // position of the first set member (after `[` and an optional `^`)
+    set_offset: usize, // position of the `[` opening the current set
+    dialect: Dialect,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(input: &'a str) -> Self {
+    pub fn new<P: AsRef<[u8]> + ?Sized>(input: &'a P) -> Self {
+        Self::with_dialect(input, Dialect::default())
+    }
+
+    /// Lexes `input` with the character classes of the given Lua version
+    pub fn with_dialect<P: AsRef<[u8]> + ?Sized>(input: &'a P, dialect: Dialect) -> Self {
         Lexer {
-            input: input.as_bytes(),
+            input: input.as_ref(),
             pos: 0,
             capture_depth: 0,
-            set_depth: 0,
+            in_set: false,
+            set_start: 0,
+            set_offset: 0,
+            dialect,
         }
     }
 
+    /// Byte offset of the next token in the pattern
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
     fn peek(&self) -> Option<u8> {
         self.input.get(self.pos).copied()
     }
@@ -66,10 +50,15 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn next_token(&mut self) -> Result<Option<Token>> {
+        let token_start = self.pos;
         let Some(byte) = self.advance() else {
             return Ok(None);
         };
 
+        if self.in_set {
+            return self.next_set_token(token_start, byte).map(Some);
+        }
+
         match byte {
             b'(' => {
                 self.capture_depth += 1;
@@ -85,107 +74,73 @@ impl<'a> Lexer<'a> {
             }
             b'.' => Ok(Some(Token::Any)),
             b'[' => {
-                self.set_depth += 1;
+                self.in_set = true;
+                self.set_start = self.pos;
+                self.set_offset = token_start;
                 Ok(Some(Token::LBracket))
             }
-            b']' => {
-                if self.set_depth > 0 {
-                    self.set_depth -= 1;
-                    Ok(Some(Token::RBracket))
-                } else {
-                    Ok(Some(Token::Literal(byte)))
-                }
-            }
-            b'^' => Ok(Some(Token::Caret)),
-            b'$' => Ok(Some(Token::Dollar)),
-            b'*' => {
-                if self.set_depth > 0 {
-                    Ok(Some(Token::Literal(b'*')))
-                } else {
-                    Ok(Some(Token::Star))
-                }
-            }
-            b'+' => {
-                if self.set_depth > 0 {
-                    Ok(Some(Token::Literal(b'+')))
-                } else {
-                    Ok(Some(Token::Plus))
+            b']' => Ok(Some(Token::Literal(byte))),
+            // `^` anchors only as the first pattern byte and `$` only as the last one,
+            // anywhere else they match themselves (as in lstrlib)
+            b'^' if token_start == 0 => Ok(Some(Token::Caret)),
+            b'$' if self.pos == self.input.len() => Ok(Some(Token::Dollar)),
+            b'*' => Ok(Some(Token::Star)),
+            b'+' => Ok(Some(Token::Plus)),
+            b'?' => Ok(Some(Token::Question)),
+            b'-' => Ok(Some(Token::Minus)),
+            b'%' => {
+                let Some(next_byte) = self.advance() else {
+                    return Err(Error::at(ErrorKind::EndsWithPercent, token_start));
+                };
+                // Like lstrlib, `%x` stands for `x` itself unless `x` is a class letter
+                // or one of `b`, `f` and the digits (e.g. `%:` or `%q`)
+                match next_byte {
+                    c if self.dialect.is_class(c) => Ok(Some(Token::Class(c))),
+                    b'b' => {
+                        let (Some(d1), Some(d2)) = (self.advance(), self.advance()) else {
+                            return Err(Error::at(
+                                ErrorKind::MissingBalancedArguments,
+                                token_start,
+                            ));
+                        };
+                        Ok(Some(Token::Balanced(d1, d2)))
+                    }
+                    b'f' => Ok(Some(Token::Frontier)),
+                    d @ b'0'..=b'9' => Ok(Some(Token::CaptureRef(d - b'0'))),
+                    c => Ok(Some(Token::EscapedLiteral(c))),
                 }
             }
-            b'?' => {
-                if self.set_depth > 0 {
-                    Ok(Some(Token::Literal(b'?')))
-                } else {
-                    Ok(Some(Token::Question))
-                }
+
+            _ => Ok(Some(Token::Literal(byte))),
+        }
+    }
+
+    /// Inside `[...]` every byte is a set member except `%` escapes and the closing `]`.
+    /// A `^` right after `[` negates the set and a `]` in the first member position is literal.
+    fn next_set_token(&mut self, token_start: usize, byte: u8) -> Result<Token> {
+        let is_first_member = token_start == self.set_start;
+
+        match byte {
+            b'^' if is_first_member && self.input[token_start - 1] == b'[' => {
+                self.set_start = self.pos;
+                Ok(Token::Caret)
             }
-            b'-' => {
-                if self.set_depth > 0 {
-                    Ok(Some(Token::Literal(b'-')))
-                } else {
-                    Ok(Some(Token::Minus))
-                }
+            b']' if !is_first_member => {
+                self.in_set = false;
+                Ok(Token::RBracket)
             }
             b'%' => {
-                if self.set_depth > 0 {
-                    if let Some(next_byte) = self.peek() {
-                        match next_byte {
-                            byte if is_class_byte(next_byte) => Ok(Some(Token::Class(byte))),
-                            byte if is_escapable_magic_byte(next_byte) => {
-                                self.advance();
-                                Ok(Some(Token::EscapedLiteral(byte)))
-                            }
-                            b'%' => {
-                                self.advance();
-                                Ok(Some(Token::EscapedLiteral(b'%')))
-                            }
-                            _ => Err(Error::Lexer(format!(
-                                "malformed pattern (invalid escape sequence in set: %{})",
-                                next_byte
-                            ))),
-                        }
-                    } else {
-                        Err(Error::Lexer(
-                            "malformed pattern (ends with '%' inside set)".to_string(),
-                        ))
-                    }
-                } else {
-                    let Some(next_byte) = self.advance() else {
-                        return Err(Error::Lexer(
-                            "malformed pattern (ends with '%')".to_string(),
-                        ));
-                    };
-                    match next_byte {
-                        c if is_escapable_magic_byte(c) => Ok(Some(Token::EscapedLiteral(c))),
-                        c if is_class_byte(c) => Ok(Some(Token::Class(c))),
-                        b'%' => {
-                            self.advance();
-                            Ok(Some(Token::EscapedLiteral(b'%')))
-                        }
-                        b'b' => {
-                            let Some(d1) = self.advance() else {
-                                return Err(Error::Lexer(
-                                    "malformed pattern (%b needs two characters)".to_string(),
-                                ));
-                            };
-                            let Some(d2) = self.advance() else {
-                                return Err(Error::Lexer(
-                                    "malformed pattern (%b needs two characters)".to_string(),
-                                ));
-                            };
-                            Ok(Some(Token::Balanced(d1, d2)))
-                        }
-                        b'f' => Ok(Some(Token::Frontier)),
-                        d @ b'1'..=b'9' => Ok(Some(Token::CaptureRef(d - b'0'))),
-                        _ => Err(Error::Lexer(format!(
-                            "malformed pattern (invalid escape sequence in set: %{})",
-                            next_byte
-                        ))),
-                    }
+                // Like lstrlib, a set cut short by the end of the pattern is reported
+                // as a missing `]` even when its last byte is `%`
+                let Some(next_byte) = self.advance() else {
+                    return Err(Error::at(ErrorKind::UnfinishedClass, self.set_offset));
+                };
+                match next_byte {
+                    c if self.dialect.is_class(c) => Ok(Token::Class(c)),
+                    c => Ok(Token::EscapedLiteral(c)),
                 }
             }
-
-            _ => Ok(Some(Token::Literal(byte))),
+            _ => Ok(Token::Literal(byte)),
         }
     }
 }
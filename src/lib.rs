@@ -2,35 +2,33 @@
 #![allow(clippy::manual_is_ascii_check)]
 
 pub mod ast;
+pub mod bytes;
+pub mod capture;
 pub mod charset;
+pub mod dialect;
 pub mod engine;
+pub mod error;
 pub mod lexer;
 pub mod lua;
+pub mod options;
 pub mod parser;
+pub mod pattern;
+pub mod value;
 
 pub use self::{
     ast::{AstNode, Quantifier},
+    capture::{Capture, Match, MatchFn},
     charset::CharSet,
+    dialect::Dialect,
+    error::{Error, ErrorKind, ReplaceError},
     lexer::{Lexer, Token},
-    lua::{Repl, find, gmatch, gsub, r#match},
+    lua::{Replacer, find, find_plain, gmatch, gsub, gsub_cow, gsub_into, r#match, try_gsub},
+    options::{Indexing, MatchOptions},
     parser::Parser,
+    pattern::Pattern,
+    value::{ReplacementValue, Value},
 };
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Error {
-    Lexer(String),
-    Parser(String),
-    Matcher(String), // TODO: Maybe remove Matcher variant if engine returns Option/Result
-}
-
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Error::Lexer(s) | Error::Parser(s) | Error::Matcher(s) => write!(f, "{}", s),
-        }
-    }
-}
-
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub const LUA_MAXCAPTURES: usize = 32;
@@ -1,30 +1,21 @@
+use super::Capture;
+
 pub mod find;
 pub mod gmatch;
 pub mod gsub;
 pub mod r#match;
 
 pub use self::{
-    find::find,
+    find::{find, find_plain},
     gmatch::gmatch,
-    gsub::{Repl, gsub},
+    gsub::{Replacer, gsub, gsub_cow, gsub_into, try_gsub},
     r#match::r#match,
 };
 
-fn calculate_start_index(text_len: usize, init: Option<isize>) -> usize {
-    match init {
-        Some(i) if i > 0 => {
-            let i = if cfg!(feature = "1-based") { i - 1 } else { i };
-            let i = i as usize;
-            if i >= text_len { text_len } else { i }
-        }
-        Some(i) if i < 0 => {
-            let abs_i = (-i) as usize;
-            if abs_i > text_len {
-                0
-            } else {
-                text_len.saturating_sub(abs_i)
-            }
-        }
-        _ => 0,
-    }
+/// Converts byte captures into owned strings, replacing invalid UTF-8 sequences
+fn to_lossy_captures(captures: Vec<Capture<&[u8]>>) -> Vec<Capture> {
+    captures
+        .into_iter()
+        .map(|capture| capture.map(|bytes| String::from_utf8_lossy(bytes).into_owned()))
+        .collect()
 }
@@ -1,81 +1,40 @@
 use super::{
-    super::{Parser, Result, engine::find_first_match},
-    calculate_start_index,
+    super::{Capture, Indexing, Pattern, Result, bytes},
+    to_lossy_captures,
 };
 
-/// Corresponds to Lua 5.3 [`string.find`].
-/// Returns 1-based or 0-based (see features [`1-based`] and [`0-based`]) indices (start, end) and captured strings. The [`init`] argument can be either 0-based or 1-based.
+/// Corresponds to Lua 5.3 `string.find`.
+/// Returns indices (start, end) and captures. Indices and the `init` argument are 1-based as in Lua,
+/// use [`find_plain`] or [`crate::MatchOptions::indexing`] on a [`Pattern`] to pick another convention.
 pub fn find(
     text: &str,
     pattern: &str,
     init: Option<isize>,
     plain: bool,
-) -> Result<Option<(usize, usize, Vec<String>)>> {
-    let text_bytes = text.as_bytes();
-    let byte_len = text_bytes.len();
-
-    let start_byte_index = calculate_start_index(byte_len, init);
-
-    if plain {
-        if pattern.is_empty() {
-            if cfg!(feature = "1-based") {
-                return Ok(Some((
-                    start_byte_index.saturating_add(1),
-                    start_byte_index,
-                    vec![],
-                )));
-            } else {
-                return Ok(Some((start_byte_index, start_byte_index, vec![])));
-            }
-        }
-
-        if start_byte_index >= byte_len {
-            return Ok(None);
-        }
-
-        if let Some(rel_byte_pos) = text_bytes[start_byte_index..]
-            .windows(pattern.len())
-            .position(|window| window == pattern.as_bytes())
-        {
-            let zero_based_start_pos = start_byte_index + rel_byte_pos;
-            let zero_based_end_pos = zero_based_start_pos + pattern.len();
-
-            let start_pos = if cfg!(feature = "1-based") {
-                zero_based_start_pos.saturating_add(1)
-            } else {
-                zero_based_start_pos
-            };
-
-            let end_pos = zero_based_end_pos;
-
-            Ok(Some((start_pos, end_pos, vec![])))
-        } else {
-            Ok(None)
-        }
-    } else {
-        let mut parser = Parser::new(pattern)?;
-        let ast = parser.parse()?;
-
-        match find_first_match(&ast, text_bytes, start_byte_index)? {
-            Some((match_byte_range, captures_byte_ranges)) => {
-                let start_pos = if cfg!(feature = "1-based") {
-                    match_byte_range.start.saturating_add(1)
-                } else {
-                    match_byte_range.start
-                };
-                let end_pos = match_byte_range.end;
+) -> Result<Option<(usize, usize, Vec<Capture>)>> {
+    let found = bytes::find(text.as_bytes(), pattern.as_bytes(), init, plain)?;
+    Ok(found.map(|(start, end, captures)| (start, end, to_lossy_captures(captures))))
+}
 
-                let captured_strings: Vec<String> = captures_byte_ranges
-                    .into_iter()
-                    .filter_map(|maybe_range| {
-                        maybe_range
-                            .map(|range| String::from_utf8_lossy(&text_bytes[range]).into_owned())
-                    })
-                    .collect();
+/// Same as [`find`] with `plain = true`, with indices and `init` following `indexing`.
+pub fn find_plain(
+    text: &str,
+    pattern: &str,
+    init: Option<isize>,
+    indexing: Indexing,
+) -> Result<Option<(usize, usize, Vec<Capture>)>> {
+    let found = bytes::find_plain(text.as_bytes(), pattern.as_bytes(), init, indexing)?;
+    Ok(found.map(|(start, end, captures)| (start, end, to_lossy_captures(captures))))
+}
 
-                Ok(Some((start_pos, end_pos, captured_strings)))
-            }
-            None => Ok(None),
-        }
+impl Pattern {
+    /// Same as [`find`] with `plain = false`, using the already compiled pattern.
+    pub fn find(
+        &self,
+        text: &str,
+        init: Option<isize>,
+    ) -> Result<Option<(usize, usize, Vec<Capture>)>> {
+        let found = self.find_bytes(text.as_bytes(), init)?;
+        Ok(found.map(|(start, end, captures)| (start, end, to_lossy_captures(captures))))
     }
 }
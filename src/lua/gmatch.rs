@@ -1,27 +1,31 @@
-use crate::{Parser, Result};
+use crate::{Pattern, Result, bytes};
+use std::borrow::Cow;
 
 mod iter;
 
 pub use iter::GMatchIterator;
 
 /// Corresponds to Lua 5.3 `string.gmatch`
-pub fn gmatch(
-    text: &str,
-    pattern: &str,
-) -> Result<GMatchIterator> {
-    let is_empty_pattern = pattern.is_empty();
-
-    let pattern_ast = if is_empty_pattern {
-        Vec::new()
-    } else {
-        let mut parser = Parser::new(pattern)?;
-        parser.parse()?
-    };
-
+pub fn gmatch<'a>(text: &'a str, pattern: &str) -> Result<GMatchIterator<'a>> {
+    let pattern = Pattern::new(pattern)?;
     Ok(GMatchIterator {
-        bytes: text.as_bytes().to_vec(),
-        pattern_ast,
-        current_pos: 0,
-        is_empty_pattern,
+        inner: bytes::GMatchIterator::new(text.as_bytes(), Cow::Owned(pattern)),
     })
 }
+
+impl Pattern {
+    /// Same as [`gmatch`], using the already compiled pattern.
+    pub fn gmatch<'a>(&'a self, text: &'a str) -> GMatchIterator<'a> {
+        GMatchIterator {
+            inner: self.gmatch_bytes(text.as_bytes()),
+        }
+    }
+
+    /// Same as [`Pattern::gmatch`], starting at `init` like Lua 5.4 `string.gmatch(s, p, init)`.
+    /// Earlier [`crate::Dialect`]s ignore `init`, as their `string.gmatch` does.
+    pub fn gmatch_from<'a>(&'a self, text: &'a str, init: Option<isize>) -> GMatchIterator<'a> {
+        GMatchIterator {
+            inner: self.gmatch_bytes_from(text.as_bytes(), init),
+        }
+    }
+}
@@ -1,88 +1,160 @@
-use super::super::{Parser, Result, engine::find_first_match};
-use repl::process_replacement_string;
+use super::super::{
+    Capture, Error, ErrorKind, Pattern, ReplaceError, ReplacementValue, Result, value::append_value,
+};
+use std::{borrow::Cow, fmt};
 
 mod repl;
 
-pub use repl::Repl;
+pub use repl::Replacer;
+use repl::call_with_args;
 
 /// Corresponds to Lua 5.3 `string.gsub`
-pub fn gsub<'a>(
-    text: &'a str,
+pub fn gsub(
+    text: &str,
     pattern: &str,
-    repl: Repl<'a>,
+    repl: impl Replacer,
     n: Option<usize>,
 ) -> Result<(String, usize)> {
-    let text_bytes = text.as_bytes();
-    let byte_len = text_bytes.len();
+    Pattern::new(pattern)?.gsub(text, repl, n)
+}
 
-    let mut parser = Parser::new(pattern)?;
-    let pattern_ast = parser.parse()?;
+/// Same as [`gsub`], borrowing `text` when no match is replaced
+pub fn gsub_cow<'t>(
+    text: &'t str,
+    pattern: &str,
+    repl: impl Replacer,
+    n: Option<usize>,
+) -> Result<(Cow<'t, str>, usize)> {
+    Pattern::new(pattern)?.gsub_cow(text, repl, n)
+}
 
-    let mut result = String::new();
-    let mut last_pos = 0;
-    let mut replacements = 0;
-    let max_replacements = n.unwrap_or(usize::MAX);
+/// Same as [`gsub`], writing the result to `dst` and returning the number of replacements.
+///
+/// On error, `dst` may have received part of the result.
+pub fn gsub_into(
+    dst: &mut impl fmt::Write,
+    text: &str,
+    pattern: &str,
+    repl: impl Replacer,
+    n: Option<usize>,
+) -> Result<usize> {
+    Pattern::new(pattern)?.gsub_into(dst, text, repl, n)
+}
 
-    while replacements < max_replacements {
-        match find_first_match(&pattern_ast, text_bytes, last_pos)? {
-            Some((match_range, captures)) => {
-                result.push_str(&text[last_pos..match_range.start]);
+/// Same as [`gsub`] with a replacement function that can fail, stops at its first error
+pub fn try_gsub<T: ReplacementValue, E>(
+    text: &str,
+    pattern: &str,
+    f: impl FnMut(&[Capture<&str>]) -> std::result::Result<T, E>,
+    n: Option<usize>,
+) -> std::result::Result<(String, usize), ReplaceError<E>> {
+    Pattern::new(pattern)?.try_gsub(text, f, n)
+}
 
-                let full_match = &text[match_range.start..match_range.end];
-                let captures_str: Vec<&str> = captures
-                    .iter()
-                    .filter_map(|maybe_range| {
-                        maybe_range
-                            .as_ref()
-                            .map(|range| &text[range.start..range.end])
-                    })
-                    .collect();
+impl Pattern {
+    /// Same as [`gsub`], using the already compiled pattern.
+    ///
+    /// The result is built at the byte level, so a match that splits a multibyte character
+    /// never panics. Function and table replacements see such captures with invalid
+    /// sequences replaced (as in [`String::from_utf8_lossy`]), and an error is returned
+    /// if the final result is not valid UTF-8 (see [`fn@crate::bytes::gsub`] for raw bytes).
+    pub fn gsub(
+        &self,
+        text: &str,
+        repl: impl Replacer,
+        n: Option<usize>,
+    ) -> Result<(String, usize)> {
+        let (result, replacements) = self.gsub_cow(text, repl, n)?;
+        Ok((result.into_owned(), replacements))
+    }
 
-                match &repl {
-                    Repl::String(repl_str) => {
-                        let replacement = process_replacement_string(repl_str, &captures_str)?;
-                        result.push_str(&replacement);
-                    }
-                    Repl::Function(f) => {
-                        let mut args = Vec::with_capacity(captures_str.len() + 1);
-                        args.push(full_match);
-                        args.extend(captures_str.iter());
-                        let replacement = f(&args);
-                        result.push_str(&replacement);
-                    }
-                    Repl::Table(table) => {
-                        let key = if !captures_str.is_empty() {
-                            captures_str[0]
-                        } else {
-                            full_match
-                        };
+    /// Same as [`gsub_cow`], using the already compiled pattern.
+    pub fn gsub_cow<'t>(
+        &self,
+        text: &'t str,
+        mut repl: impl Replacer,
+        n: Option<usize>,
+    ) -> Result<(Cow<'t, str>, usize)> {
+        let mut result = Vec::new();
+        let replacements = self.gsub_with(
+            text.as_bytes(),
+            n,
+            &mut result,
+            |m, result| repl.replace_append(m, result),
+            |_| Ok(()),
+        )?;
 
-                        if let Some(replacement) = table.get(key) {
-                            result.push_str(replacement);
-                        } else {
-                            result.push_str(full_match);
-                        }
-                    }
-                }
+        if replacements == 0 {
+            return Ok((Cow::Borrowed(text), 0));
+        }
+        let result = String::from_utf8(result).map_err(|_| Error::new(ErrorKind::InvalidUtf8))?;
+        Ok((Cow::Owned(result), replacements))
+    }
 
-                last_pos = match_range.end;
-                replacements += 1;
+    /// Same as [`gsub_into`], using the already compiled pattern.
+    pub fn gsub_into(
+        &self,
+        dst: &mut impl fmt::Write,
+        text: &str,
+        mut repl: impl Replacer,
+        n: Option<usize>,
+    ) -> Result<usize> {
+        let mut buffer = Vec::new();
+        let replacements = self.gsub_with(
+            text.as_bytes(),
+            n,
+            &mut buffer,
+            |m, buffer| repl.replace_append(m, buffer),
+            |buffer| write_utf8_prefix(dst, buffer),
+        )?;
 
-                if match_range.start == match_range.end {
-                    if last_pos >= byte_len {
-                        break;
-                    }
-                    result.push_str(&text[last_pos..last_pos + 1]);
-                    last_pos += 1;
-                }
-            }
-            None => break,
+        if replacements == 0 {
+            dst.write_str(text)
+                .map_err(|_| Error::new(ErrorKind::WriteFailed))?;
+        } else if !buffer.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidUtf8));
         }
+        Ok(replacements)
     }
 
-    if last_pos < byte_len {
-        result.push_str(&text[last_pos..]);
+    /// Same as [`try_gsub`], using the already compiled pattern.
+    pub fn try_gsub<T: ReplacementValue, E>(
+        &self,
+        text: &str,
+        mut f: impl FnMut(&[Capture<&str>]) -> std::result::Result<T, E>,
+        n: Option<usize>,
+    ) -> std::result::Result<(String, usize), ReplaceError<E>> {
+        let mut result = Vec::new();
+        let replacements = self.gsub_with(
+            text.as_bytes(),
+            n,
+            &mut result,
+            |m, result| {
+                let value = call_with_args(m, &mut f).map_err(ReplaceError::Replacement)?;
+                append_value(value.to_value(), m, result).map_err(ReplaceError::Pattern)
+            },
+            |_| Ok(()),
+        )?;
+
+        if replacements == 0 {
+            return Ok((text.to_string(), 0));
+        }
+        let result = String::from_utf8(result).map_err(|_| Error::new(ErrorKind::InvalidUtf8))?;
+        Ok((result, replacements))
     }
+}
 
-    Ok((result, replacements))
+/// Writes the longest valid UTF-8 prefix of `buffer` to `dst` and removes it from `buffer`.
+/// An incomplete character at the end stays buffered, a match may have split it.
+fn write_utf8_prefix(dst: &mut impl fmt::Write, buffer: &mut Vec<u8>) -> Result<()> {
+    let valid_len = match std::str::from_utf8(buffer) {
+        Ok(valid) => valid.len(),
+        Err(err) if err.error_len().is_none() => err.valid_up_to(),
+        Err(_) => return Err(Error::new(ErrorKind::InvalidUtf8)),
+    };
+    let valid = std::str::from_utf8(&buffer[..valid_len]).expect("prefix was validated");
+    dst.write_str(valid)
+        .map_err(|_| Error::new(ErrorKind::WriteFailed))?;
+    buffer.drain(..valid_len);
+    Ok(())
 }
@@ -1,61 +1,113 @@
-use crate::Result;
-use std::collections::HashMap;
-
-pub enum Repl<'a> {
-    String(&'a str),
-    Function(Box<dyn Fn(&[&str]) -> String + 'a>),
-    Table(&'a HashMap<String, String>),
-}
-
-enum ReplToken {
-    Literal(u8),
-    CaptureRef(usize),
-}
-
-pub fn process_replacement_string(repl: &str, captures: &[&str]) -> Result<String> {
-    let tokens = tokenize_replacement_string(repl);
-    let mut result = String::with_capacity(tokens.len());
-
-    for token in tokens {
-        match token {
-            ReplToken::Literal(b) => {
-                result.push(b as char);
-            }
-            ReplToken::CaptureRef(idx) => {
-                if idx <= captures.len() {
-                    result.push_str(captures[idx - 1]);
-                }
-            }
-        }
-    }
-
-    Ok(result)
-}
-
-fn tokenize_replacement_string(repl: &str) -> Vec<ReplToken> {
-    let mut tokens = Vec::new();
-    let bytes = repl.as_bytes();
-    let mut i = 0;
-
-    while i < bytes.len() {
-        if bytes[i] == b'%' && i + 1 < bytes.len() {
-            let next_byte = bytes[i + 1];
-            if (b'1'..=b'9').contains(&next_byte) {
-                let capture_idx = (next_byte - b'0') as usize;
-                tokens.push(ReplToken::CaptureRef(capture_idx));
-                i += 2;
-            } else if next_byte == b'%' {
-                tokens.push(ReplToken::Literal(b'%'));
-                i += 2;
-            } else {
-                tokens.push(ReplToken::Literal(b'%'));
-                i += 1;
-            }
-        } else {
-            tokens.push(ReplToken::Literal(bytes[i]));
-            i += 1;
-        }
-    }
-
-    tokens
+use crate::{
+    Capture, Match, MatchFn, ReplacementValue, Result, bytes::gsub::process_replacement_string,
+    value::append_value,
+};
+use std::{
+    borrow::{Borrow, Cow},
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// Produces the replacement of each match in [`gsub`](super::gsub).
+///
+/// Implemented for replacement strings (`&str`, `String`), functions receiving the
+/// full match followed by the captures, and `HashMap`/`BTreeMap` tables keyed by the
+/// first capture or the whole match. Functions and tables produce a [`ReplacementValue`],
+/// e.g. `None` keeps the match. Wrap a function in [`MatchFn`] to receive the whole
+/// [`Match`], or implement this trait for custom lookups, which report a failure
+/// with [`crate::Error::replacer`].
+///
+/// Replacements are appended as bytes since a match may split a multibyte character,
+/// [`gsub`](super::gsub) checks that the final result is valid UTF-8.
+pub trait Replacer {
+    /// Appends the replacement of `m` to `dst`
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()>;
+}
+
+impl Replacer for &str {
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        process_replacement_string(self.as_bytes(), m.as_bytes(), &m.captures, m.dialect, dst)
+    }
+}
+
+impl Replacer for String {
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        self.as_str().replace_append(m, dst)
+    }
+}
+
+/// Calls a replacement function with the full match followed by the captures,
+/// invalid UTF-8 sequences replaced as in [`String::from_utf8_lossy`]
+pub(super) fn call_with_args<R>(m: &Match<'_>, f: impl FnOnce(&[Capture<&str>]) -> R) -> R {
+    let args: Vec<Capture<Cow<str>>> = std::iter::once(Capture::Str(m.as_bytes()))
+        .chain(m.captures.iter().cloned())
+        .map(|capture| capture.map(String::from_utf8_lossy))
+        .collect();
+    let args: Vec<Capture<&str>> = args.iter().map(|capture| capture.as_deref()).collect();
+    f(&args)
+}
+
+impl<F, T> Replacer for F
+where
+    F: FnMut(&[Capture<&str>]) -> T,
+    T: ReplacementValue,
+{
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        let value = call_with_args(m, self);
+        append_value(value.to_value(), m, dst)
+    }
+}
+
+impl<F, T> Replacer for MatchFn<F>
+where
+    F: FnMut(&Match<'_>) -> T,
+    T: ReplacementValue,
+{
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        append_value((self.0)(m).to_value(), m, dst)
+    }
+}
+
+/// Looks up the first capture (or the whole match) with `get`, a missing key keeps the match
+fn replace_from_table<V: ReplacementValue>(
+    m: &Match<'_>,
+    dst: &mut Vec<u8>,
+    get: impl FnOnce(&str) -> Option<V>,
+) -> Result<()> {
+    let key = m.first_capture().map(String::from_utf8_lossy).to_string();
+    append_value(get(&key).to_value(), m, dst)
+}
+
+impl<K, V, S> Replacer for &HashMap<K, V, S>
+where
+    K: Borrow<str> + Eq + Hash,
+    V: ReplacementValue,
+    S: BuildHasher,
+{
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        replace_from_table(m, dst, |key| self.get(key))
+    }
+}
+
+impl<K, V, S> Replacer for HashMap<K, V, S>
+where
+    K: Borrow<str> + Eq + Hash,
+    V: ReplacementValue,
+    S: BuildHasher,
+{
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        (&*self).replace_append(m, dst)
+    }
+}
+
+impl<K: Borrow<str> + Ord, V: ReplacementValue> Replacer for &BTreeMap<K, V> {
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        replace_from_table(m, dst, |key| self.get(key))
+    }
+}
+
+impl<K: Borrow<str> + Ord, V: ReplacementValue> Replacer for BTreeMap<K, V> {
+    fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+        (&*self).replace_append(m, dst)
+    }
 }
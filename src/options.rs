@@ -0,0 +1,127 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+use super::{Error, ErrorKind, Result};
+
+/// How often (in steps) the deadline and the cancel flag are checked
+const CHECK_INTERVAL: u64 = 1024;
+
+/// How positions are passed to and returned from the matching functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Indexing {
+    /// Lua convention: the first byte is at 1, `find` returns inclusive end positions
+    OneBased,
+    /// The first byte is at 0, `find` returns exclusive end positions
+    ZeroBased,
+}
+
+impl Default for Indexing {
+    /// [`Indexing::OneBased`], as in Lua
+    fn default() -> Self {
+        Indexing::OneBased
+    }
+}
+
+impl Indexing {
+    /// Turns a Lua `init` argument into a 0-based byte index, negative values
+    /// count from the end of the subject
+    pub(crate) fn start_index(self, text_len: usize, init: Option<isize>) -> usize {
+        self.unclamped_start_index(text_len, init).min(text_len)
+    }
+
+    /// Same as [`Indexing::start_index`], except that an `init` past the end of the
+    /// subject gives `text_len + 1`, where Lua 5.4 `gmatch` finds nothing
+    pub(crate) fn gmatch_start_index(self, text_len: usize, init: Option<isize>) -> usize {
+        self.unclamped_start_index(text_len, init).min(text_len + 1)
+    }
+
+    fn unclamped_start_index(self, text_len: usize, init: Option<isize>) -> usize {
+        match init {
+            Some(i) if i > 0 => match self {
+                Indexing::OneBased => i as usize - 1,
+                Indexing::ZeroBased => i as usize,
+            },
+            Some(i) if i < 0 => text_len.saturating_sub(i.unsigned_abs()),
+            _ => 0,
+        }
+    }
+
+    /// Turns a 0-based byte index into a position returned to the caller
+    pub(crate) fn position(self, pos: usize) -> usize {
+        match self {
+            Indexing::OneBased => pos + 1,
+            Indexing::ZeroBased => pos,
+        }
+    }
+}
+
+/// Settings for matching, set with [`crate::Pattern::with_options`].
+///
+/// Besides [`Indexing`], these are limits for matching untrusted patterns.
+/// A step is one start position tried or one backtrack into an alternative.
+/// The step count starts over for every match search, so `gsub` and `gmatch`
+/// get a fresh budget for each match they look for, while the deadline and the
+/// cancel flag apply to the whole call.
+#[derive(Debug, Clone, Default)]
+pub struct MatchOptions {
+    /// Maximum number of steps for a single match search
+    pub max_steps: Option<u64>,
+    /// Point in time after which matching is abandoned
+    pub deadline: Option<Instant>,
+    /// Matching is abandoned once this flag is set, e.g. from another thread
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Defaults to [`Indexing::OneBased`]
+    pub indexing: Indexing,
+}
+
+impl MatchOptions {
+    /// Called by the engine after every step
+    #[inline]
+    pub(crate) fn check(&self, steps: u64) -> Result<()> {
+        if self.max_steps.is_some_and(|max| steps > max) {
+            return Err(Error::new(ErrorKind::PatternTooComplex));
+        }
+        if steps.is_multiple_of(CHECK_INTERVAL) {
+            self.check_interrupt()?;
+        }
+        Ok(())
+    }
+
+    /// Checks the deadline and the cancel flag. The engine also calls this before every
+    /// match search, so `gsub` and `gmatch` stop even when each match takes few steps.
+    pub(crate) fn check_interrupt(&self) -> Result<()> {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(Error::new(ErrorKind::PatternTooComplex));
+        }
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            return Err(Error::new(ErrorKind::Cancelled));
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for MatchOptions {
+    fn eq(&self, other: &Self) -> bool {
+        let same_cancel = match (&self.cancel, &other.cancel) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        self.max_steps == other.max_steps
+            && self.deadline == other.deadline
+            && same_cancel
+            && self.indexing == other.indexing
+    }
+}
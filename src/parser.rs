@@ -1,10 +1,9 @@
 use super::{
-    Error, LUA_MAXCAPTURES, Result,
+    Dialect, Error, ErrorKind, LUA_MAXCAPTURES, Result,
     ast::{AstNode, Quantifier},
     charset::CharSet,
     lexer::{Lexer, Token},
 };
-use std::iter::Peekable;
 
 const fn token_to_byte(token: &Token) -> u8 {
     match token {
@@ -30,68 +29,102 @@ const fn token_to_byte(token: &Token) -> u8 {
 }
 
 pub struct Parser {
-    tokens: Peekable<std::vec::IntoIter<Token>>,
+    tokens: Vec<Token>,
+    offsets: Vec<usize>, // byte offset in the pattern of each token
+    end_offset: usize,
+    pos: usize,
     capture_count: usize,
+    dialect: Dialect,
 }
 
 impl Parser {
-    pub fn new(pattern: &str) -> Result<Self> {
-        let mut lexer = Lexer::new(pattern);
-        let mut token_vec = Vec::new();
+    pub fn new<P: AsRef<[u8]> + ?Sized>(pattern: &P) -> Result<Self> {
+        Self::with_dialect(pattern, Dialect::default())
+    }
+
+    /// Parses `pattern` following the rules of the given Lua version
+    pub fn with_dialect<P: AsRef<[u8]> + ?Sized>(pattern: &P, dialect: Dialect) -> Result<Self> {
+        let mut lexer = Lexer::with_dialect(pattern, dialect);
+        let mut tokens = Vec::new();
+        let mut offsets = Vec::new();
         loop {
-            match lexer.next_token() {
-                Ok(Some(token)) => token_vec.push(token),
-                Ok(None) => break,
-                Err(e) => return Err(e),
+            let offset = lexer.offset();
+            match lexer.next_token()? {
+                Some(token) => {
+                    tokens.push(token);
+                    offsets.push(offset);
+                }
+                None => break,
             }
         }
         Ok(Parser {
-            tokens: token_vec.into_iter().peekable(),
+            tokens,
+            offsets,
+            end_offset: lexer.offset(),
+            pos: 0,
             capture_count: 0,
+            dialect,
         })
     }
 
+    /// Reads a leading `^` as a literal, as lstrlib does for `gmatch`
+    pub(crate) fn without_anchor(mut self) -> Self {
+        if let Some(first @ Token::Caret) = self.tokens.first_mut() {
+            *first = Token::Literal(b'^');
+        }
+        self
+    }
+
     pub fn parse(&mut self) -> Result<Vec<AstNode>> {
-        let ast = self.parse_sequence(None)?;
+        self.parse_sequence(None)
+    }
 
-        if let Some(token) = self.tokens.peek() {
-            return Err(Error::Parser(format!(
-                "malformed pattern (unexpected token {:?} after end of pattern)",
-                token
-            )));
-        }
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
 
-        if self.capture_count > LUA_MAXCAPTURES {
-            return Err(Error::Parser(format!(
-                "pattern has too many captures (limit is {})",
-                LUA_MAXCAPTURES
-            )));
+    fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + n)
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        if token.is_some() {
+            self.pos += 1;
         }
+        token
+    }
 
-        Ok(ast)
+    /// Byte offset of the next token in the pattern
+    fn offset(&self) -> usize {
+        self.offsets
+            .get(self.pos)
+            .copied()
+            .unwrap_or(self.end_offset)
     }
 
     fn parse_sequence(&mut self, end_token: Option<&Token>) -> Result<Vec<AstNode>> {
         let mut ast = Vec::new();
 
-        while self.tokens.peek().is_some() && self.tokens.peek() != end_token {
+        while self.peek().is_some() && self.peek() != end_token {
             ast.push(self.parse_item()?);
         }
 
-        if end_token.is_some() && self.tokens.peek() != end_token {
-            return Err(Error::Parser(format!(
-                "malformed pattern (unexpected end, expected {:?})",
-                end_token.unwrap()
-            )));
-        }
-
         Ok(ast)
     }
 
     fn parse_item(&mut self) -> Result<AstNode> {
         let mut base_item = self.parse_base()?;
+        if !matches!(
+            base_item,
+            AstNode::Literal(_) | AstNode::Any | AstNode::Class(_, _) | AstNode::Set(_)
+        ) {
+            // Like lstrlib, only single-byte items take a quantifier, after anchors,
+            // captures, `%b`, `%f` and back-references a quantifier starts the next item
+            return Ok(base_item);
+        }
 
-        let quantifier = match self.tokens.peek() {
+        let quantifier = match self.peek() {
             Some(Token::Star) => Some(Quantifier::Star),
             Some(Token::Plus) => Some(Quantifier::Plus),
             Some(Token::Question) => Some(Quantifier::Question),
@@ -100,16 +133,7 @@ impl Parser {
         };
 
         if let Some(q) = quantifier {
-            self.tokens.next();
-
-            match base_item {
-                AstNode::AnchorStart | AstNode::AnchorEnd | AstNode::Frontier(_) => {
-                    return Err(Error::Parser(
-                        "pattern item cannot be quantified".to_string(),
-                    ));
-                }
-                _ => {}
-            }
+            self.next_token();
 
             base_item = AstNode::Quantified {
                 item: Box::new(base_item),
@@ -121,47 +145,39 @@ impl Parser {
     }
 
     fn parse_base(&mut self) -> Result<AstNode> {
-        let Some(token) = self.tokens.next() else {
-            return Err(Error::Parser("unexpected end of pattern".to_string()));
+        let offset = self.offset();
+        let Some(token) = self.next_token() else {
+            unreachable!("parse_base is only called when a token is available");
         };
 
         match token {
-            Token::Literal(b')') => Err(Error::Parser(
-                "malformed pattern (unexpected ')')".to_string(),
-            )),
-            Token::Literal(b']') => Err(Error::Parser(
-                "malformed pattern (unexpected ']')".to_string(),
-            )),
+            Token::Literal(b')') | Token::RParen => {
+                Err(Error::at(ErrorKind::UnmatchedCaptureClose, offset))
+            }
             Token::Literal(b) => Ok(AstNode::Literal(b)),
             Token::EscapedLiteral(b) => Ok(AstNode::Literal(b)),
             Token::Any => Ok(AstNode::Any),
             Token::Caret => Ok(AstNode::AnchorStart),
             Token::Dollar => Ok(AstNode::AnchorEnd),
 
-            Token::Class(c) => {
-                let negated = c.is_ascii_uppercase();
-                let base_byte = if negated { c.to_ascii_lowercase() } else { c };
-                if [b'a', b'c', b'd', b'g', b'l', b'p', b's', b'u', b'w', b'x'].contains(&base_byte)
-                {
-                    Ok(AstNode::Class(base_byte, negated))
-                } else {
-                    Ok(AstNode::Literal(c))
-                }
-            }
+            // The lexer only emits classes of the dialect, other letters are literals
+            Token::Class(c) => Ok(AstNode::Class(
+                c.to_ascii_lowercase(),
+                c.is_ascii_uppercase(),
+            )),
 
-            Token::LBracket => self.parse_set(),
+            Token::LBracket => self.parse_set(offset),
 
-            Token::LParen => self.parse_capture(),
+            Token::LParen => self.parse_capture(offset),
 
             Token::Balanced(d1, d2) => Ok(AstNode::Balanced(d1, d2)),
             Token::Frontier => {
-                if self.tokens.peek() != Some(&Token::LBracket) {
-                    return Err(Error::Parser(
-                        "malformed pattern (missing '[' after %f)".to_string(),
-                    ));
+                if self.peek() != Some(&Token::LBracket) {
+                    return Err(Error::at(ErrorKind::MissingBracketAfterFrontier, offset));
                 }
-                self.tokens.next();
-                let set_node = self.parse_set()?;
+                let set_offset = self.offset();
+                self.next_token();
+                let set_node = self.parse_set(set_offset)?;
                 if let AstNode::Set(charset) = set_node {
                     Ok(AstNode::Frontier(charset))
                 } else {
@@ -169,95 +185,55 @@ impl Parser {
                 }
             }
 
-            Token::RParen => Err(Error::Parser(
-                "invalid pattern (unexpected ')')".to_string(),
-            )),
-            Token::RBracket => Err(Error::Parser(
-                "invalid pattern (unexpected ']')".to_string(),
-            )),
-            Token::Star | Token::Plus | Token::Question => Err(Error::Parser(format!(
-                "invalid pattern (quantifier '{}' must follow an item)",
-                token_to_byte(&token)
-            ))),
-            Token::Minus => Ok(AstNode::Literal(b'-')),
-            Token::Percent => Err(Error::Parser(
-                "internal error: Percent token should not reach parser base".to_string(),
-            )),
-            Token::CaptureRef(n) => Ok(AstNode::CaptureRef(n as usize)),
+            Token::RBracket => Ok(AstNode::Literal(b']')),
+            // A quantifier byte that starts an item matches itself, as in lstrlib
+            Token::Star | Token::Plus | Token::Question | Token::Minus => {
+                Ok(AstNode::Literal(token_to_byte(&token)))
+            }
+            Token::Percent => unreachable!("the lexer never emits a bare Percent token"),
+            Token::CaptureRef(n) => Ok(AstNode::CaptureRef {
+                index: n as usize,
+                offset,
+            }),
         }
     }
 
-    fn parse_set(&mut self) -> Result<AstNode> {
+    /// Parses the members of a set, `open_offset` is the offset of its `[`
+    fn parse_set(&mut self, open_offset: usize) -> Result<AstNode> {
         let mut set = CharSet::new();
         let mut negated = false;
 
-        if self.tokens.peek() == Some(&Token::Caret) {
-            self.tokens.next();
+        if self.peek() == Some(&Token::Caret) {
+            self.next_token();
             negated = true;
         }
 
-        if self.tokens.peek() == Some(&Token::RBracket) {
-            self.tokens.next();
-            if negated {
-                set.invert();
-            }
-            return Ok(AstNode::Set(set));
-        }
-
-        if self.tokens.peek() == Some(&Token::RBracket) {
-            self.tokens.next();
-            set.add_byte(b']');
-        }
-
-        while self.tokens.peek().is_some() && self.tokens.peek() != Some(&Token::RBracket) {
-            match self.tokens.peek().cloned() {
+        while self.peek().is_some() && self.peek() != Some(&Token::RBracket) {
+            let offset = self.offset();
+            match self.next_token() {
                 Some(Token::Class(c)) => {
-                    self.tokens.next();
-                    set.add_class(c)?;
+                    set.add_class(c, self.dialect)
+                        .map_err(|err| Error::at(err.kind(), offset))?;
                 }
                 Some(Token::Literal(b)) => {
-                    let current_byte = b;
-                    self.tokens.next();
-
-                    if self.tokens.peek() == Some(&Token::Literal(b'-')) {
-                        let mut iter_clone = self.tokens.clone();
-                        iter_clone.next();
-
-                        if let Some(Token::Literal(next_b)) = iter_clone.peek() {
-                            let next_b_val = *next_b;
-                            self.tokens.next();
-                            self.tokens.next();
-                            set.add_range(current_byte, next_b_val)?;
-                        } else {
-                            set.add_byte(current_byte);
-                        }
+                    if let (Some(Token::Literal(b'-')), Some(Token::Literal(end))) =
+                        (self.peek(), self.peek_nth(1))
+                    {
+                        let end = *end;
+                        self.next_token();
+                        self.next_token();
+                        set.add_range(b, end);
                     } else {
-                        set.add_byte(current_byte);
+                        set.add_byte(b);
                     }
                 }
-                Some(Token::Minus) => {
-                    self.tokens.next();
-                    set.add_byte(b'-');
-                }
-                Some(Token::Percent) => {
-                    self.tokens.next();
-                    set.add_byte(b'%');
-                }
-                Some(_) => {
-                    let token = self.tokens.next().unwrap(); // TODO: remove `unwrap`
-                    let byte = token_to_byte(&token);
-                    set.add_byte(byte)
-                }
+                Some(token) => set.add_byte(token_to_byte(&token)),
                 None => unreachable!(),
             }
         }
 
-        if self.tokens.peek() == Some(&Token::RBracket) {
-            self.tokens.next();
-        } else {
-            return Err(Error::Parser(
-                "malformed pattern (unfinished character class)".to_string(),
-            ));
+        if self.next_token() != Some(Token::RBracket) {
+            return Err(Error::at(ErrorKind::UnfinishedClass, open_offset));
         }
 
         if negated {
@@ -267,22 +243,18 @@ impl Parser {
         Ok(AstNode::Set(set))
     }
 
-    fn parse_capture(&mut self) -> Result<AstNode> {
+    /// Parses a capture body and its `)`, `open_offset` is the offset of its `(`
+    fn parse_capture(&mut self, open_offset: usize) -> Result<AstNode> {
         self.capture_count += 1;
         let index = self.capture_count;
         if index > LUA_MAXCAPTURES {
-            return Err(Error::Parser(format!(
-                "pattern has too many captures (limit is {})",
-                LUA_MAXCAPTURES
-            )));
+            return Err(Error::at(ErrorKind::TooManyCaptures, open_offset));
         }
 
         let inner_ast = self.parse_sequence(Some(&Token::RParen))?;
 
-        if self.tokens.next() != Some(Token::RParen) {
-            return Err(Error::Parser(
-                "malformed pattern (unclosed capture group)".to_string(),
-            ));
+        if self.next_token() != Some(Token::RParen) {
+            return Err(Error::at(ErrorKind::UnfinishedCapture, open_offset));
         }
 
         Ok(AstNode::Capture {
@@ -0,0 +1,138 @@
+use super::{
+    AstNode, Dialect, Error, LUA_MAXCAPTURES, MatchOptions, Parser, Result,
+    engine::{Program, search},
+};
+use std::{ops::Range, str::FromStr};
+
+/// A parsed and validated Lua pattern that can be reused across many subjects.
+///
+/// The free functions in [`crate::lua`] parse their pattern on every call,
+/// build a [`Pattern`] once instead when the same pattern is applied repeatedly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub(crate) ast: Vec<AstNode>,
+    pub(crate) program: Program,
+    /// Program for `gmatch` when the pattern starts with `^`, which `gmatch` matches literally
+    pub(crate) gmatch_program: Option<Program>,
+    pub(crate) capture_count: usize,
+    pub(crate) position_captures: [bool; LUA_MAXCAPTURES],
+    pub(crate) options: MatchOptions,
+    pub(crate) dialect: Dialect,
+}
+
+impl Pattern {
+    pub fn new<P: AsRef<[u8]> + ?Sized>(pattern: &P) -> Result<Self> {
+        Self::with_dialect(pattern, Dialect::default())
+    }
+
+    /// Parses `pattern` and matches it the way the given Lua version does:
+    /// the available classes, the `gsub`/`gmatch` handling of empty matches
+    /// and the `init` argument of [`Pattern::gmatch_from`] depend on it
+    pub fn with_dialect<P: AsRef<[u8]> + ?Sized>(pattern: &P, dialect: Dialect) -> Result<Self> {
+        let mut parser = Parser::with_dialect(pattern, dialect)?;
+        let ast = parser.parse()?;
+
+        let mut capture_count = 0;
+        let mut position_captures = [false; LUA_MAXCAPTURES];
+        analyze_captures(&ast, &mut capture_count, &mut position_captures);
+
+        let gmatch_program = match ast.first() {
+            Some(AstNode::AnchorStart) => {
+                let gmatch_ast = Parser::with_dialect(pattern, dialect)?
+                    .without_anchor()
+                    .parse()?;
+                Some(Program::compile(&gmatch_ast))
+            }
+            _ => None,
+        };
+
+        Ok(Pattern {
+            program: Program::compile(&ast),
+            gmatch_program,
+            ast,
+            capture_count,
+            position_captures,
+            options: MatchOptions::default(),
+            dialect,
+        })
+    }
+
+    /// Sets the limits used by every matching method of this pattern
+    pub fn with_options(mut self, options: MatchOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &MatchOptions {
+        &self.options
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    pub fn ast(&self) -> &[AstNode] {
+        &self.ast
+    }
+
+    pub fn capture_count(&self) -> usize {
+        self.capture_count
+    }
+
+    /// Finds the first match at or after `start`, honoring the pattern's options
+    pub(crate) fn search(
+        &self,
+        text: &[u8],
+        start: usize,
+    ) -> Result<Option<(Range<usize>, Vec<Option<Range<usize>>>)>> {
+        search(&self.program, &self.options, text, start)
+    }
+
+    /// Same as [`Pattern::search`] for `gmatch`, which never anchors
+    pub(crate) fn gmatch_search(
+        &self,
+        text: &[u8],
+        start: usize,
+    ) -> Result<Option<(Range<usize>, Vec<Option<Range<usize>>>)>> {
+        let program = self.gmatch_program.as_ref().unwrap_or(&self.program);
+        search(program, &self.options, text, start)
+    }
+}
+
+fn analyze_captures(
+    ast: &[AstNode],
+    capture_count: &mut usize,
+    positions: &mut [bool; LUA_MAXCAPTURES],
+) {
+    for node in ast {
+        if let AstNode::Capture { index, inner } = node {
+            *capture_count = (*capture_count).max(*index);
+            positions[*index - 1] = inner.is_empty();
+            analyze_captures(inner, capture_count, positions);
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(pattern: &str) -> Result<Self> {
+        Pattern::new(pattern)
+    }
+}
+
+impl TryFrom<&str> for Pattern {
+    type Error = Error;
+
+    fn try_from(pattern: &str) -> Result<Self> {
+        Pattern::new(pattern)
+    }
+}
+
+impl TryFrom<&[u8]> for Pattern {
+    type Error = Error;
+
+    fn try_from(pattern: &[u8]) -> Result<Self> {
+        Pattern::new(pattern)
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{Dialect, Error, ErrorKind, Match, Result};
+use std::borrow::Cow;
+
+/// A Lua value produced by a `gsub` replacement function or table.
+///
+/// As in Lua, `nil` and `false` keep the original match, strings and numbers are
+/// inserted, and any other value raises "invalid replacement value".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value<'a> {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    Str(&'a [u8]),
+    /// Any other Lua type, by its name (e.g. `"table"`)
+    Other(&'static str),
+}
+
+/// Types a replacement function may return or a replacement table may hold
+pub trait ReplacementValue {
+    fn to_value(&self) -> Value<'_>;
+}
+
+impl ReplacementValue for Value<'_> {
+    fn to_value(&self) -> Value<'_> {
+        *self
+    }
+}
+
+impl<T: ReplacementValue + ?Sized> ReplacementValue for &T {
+    fn to_value(&self) -> Value<'_> {
+        (**self).to_value()
+    }
+}
+
+impl<T: ReplacementValue> ReplacementValue for Option<T> {
+    fn to_value(&self) -> Value<'_> {
+        match self {
+            Some(value) => value.to_value(),
+            None => Value::Nil,
+        }
+    }
+}
+
+impl ReplacementValue for str {
+    fn to_value(&self) -> Value<'_> {
+        Value::Str(self.as_bytes())
+    }
+}
+
+impl ReplacementValue for String {
+    fn to_value(&self) -> Value<'_> {
+        Value::Str(self.as_bytes())
+    }
+}
+
+impl ReplacementValue for Cow<'_, str> {
+    fn to_value(&self) -> Value<'_> {
+        Value::Str(self.as_bytes())
+    }
+}
+
+impl ReplacementValue for [u8] {
+    fn to_value(&self) -> Value<'_> {
+        Value::Str(self)
+    }
+}
+
+impl ReplacementValue for Vec<u8> {
+    fn to_value(&self) -> Value<'_> {
+        Value::Str(self)
+    }
+}
+
+impl ReplacementValue for bool {
+    fn to_value(&self) -> Value<'_> {
+        Value::Boolean(*self)
+    }
+}
+
+macro_rules! integer_values {
+    ($($t:ty),*) => {$(
+        impl ReplacementValue for $t {
+            fn to_value(&self) -> Value<'_> {
+                Value::Integer(*self as i64)
+            }
+        }
+    )*};
+}
+
+integer_values!(i8, i16, i32, i64, isize, u8, u16, u32, usize);
+
+impl ReplacementValue for f32 {
+    fn to_value(&self) -> Value<'_> {
+        Value::Float(*self as f64)
+    }
+}
+
+impl ReplacementValue for f64 {
+    fn to_value(&self) -> Value<'_> {
+        Value::Float(*self)
+    }
+}
+
+/// Appends `value` in place of `full_match` the way lstrlib's `add_value` does
+pub(crate) fn append_value(value: Value<'_>, m: &Match<'_>, dst: &mut Vec<u8>) -> Result<()> {
+    let dialect = m.dialect;
+    match value {
+        Value::Nil | Value::Boolean(false) => dst.extend_from_slice(m.as_bytes()),
+        Value::Str(s) => dst.extend_from_slice(s),
+        Value::Integer(i) if dialect >= Dialect::Lua53 => {
+            dst.extend_from_slice(i.to_string().as_bytes())
+        }
+        // Before 5.3 every number is a float
+        Value::Integer(i) => dst.extend_from_slice(format_float(i as f64, dialect).as_bytes()),
+        Value::Float(f) => dst.extend_from_slice(format_float(f, dialect).as_bytes()),
+        Value::Boolean(true) => {
+            return Err(Error::new(ErrorKind::InvalidReplacementValue("boolean")));
+        }
+        Value::Other(type_name) => {
+            return Err(Error::new(ErrorKind::InvalidReplacementValue(type_name)));
+        }
+    }
+    Ok(())
+}
+
+/// Formats a float like Lua's `tostring`: C's `%.14g`, plus `.0` for integral values since 5.3
+fn format_float(f: f64, dialect: Dialect) -> String {
+    if f.is_nan() {
+        return if f.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    }
+    if f.is_infinite() {
+        return if f < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+
+    const PRECISION: i32 = 14;
+    let scientific = format!("{:.*e}", PRECISION as usize - 1, f);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+
+    let formatted = if !(-4..PRECISION).contains(&exponent) {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim_fraction(mantissa), sign, exponent.abs())
+    } else {
+        let decimals = (PRECISION - 1 - exponent) as usize;
+        trim_fraction(&format!("{:.*}", decimals, f)).to_string()
+    };
+
+    if dialect >= Dialect::Lua53 && formatted.bytes().all(|b| b == b'-' || b.is_ascii_digit()) {
+        formatted + ".0"
+    } else {
+        formatted
+    }
+}
+
+/// Drops trailing zeros after the decimal point, and the point itself if nothing is left
+fn trim_fraction(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
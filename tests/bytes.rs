@@ -0,0 +1,132 @@
+use lsonar::{
+    Capture, Error, ErrorKind, Pattern, ReplaceError,
+    bytes::{find, gmatch, gsub, gsub_cow, gsub_into, r#match, try_gsub},
+};
+use std::{borrow::Cow, collections::HashMap};
+
+fn bvec(items: &[&'static [u8]]) -> Vec<Capture<&'static [u8]>> {
+    items.iter().map(|&s| Capture::Str(s)).collect()
+}
+
+#[test]
+fn test_bytes_find() {
+    assert_eq!(
+        find(b"\x00\xff\xfeabc", b"[\x80-\xff]+", None, false),
+        Ok(Some((2, 3, vec![])))
+    );
+    assert_eq!(
+        find(b"caf\xe9 au lait", b"(%a+)\xe9", None, false),
+        Ok(Some((1, 4, bvec(&[b"caf"]))))
+    );
+    assert_eq!(
+        find(b"a\x00b\x00c", b"\x00", Some(3), true),
+        Ok(Some((4, 4, vec![])))
+    );
+    assert_eq!(
+        find(b"\x01\x02()", b"()%b()", None, false),
+        Ok(Some((3, 4, vec![Capture::Position(3)])))
+    );
+}
+
+#[test]
+fn test_bytes_match_keeps_invalid_utf8() {
+    assert_eq!(
+        r#match(b"key=\xff\xfe\x00", b"=(.*)", None),
+        Ok(Some(bvec(&[b"\xff\xfe\x00"])))
+    );
+    assert_eq!(
+        r#match(b"\xc3\xa9t\xc3\xa9", b".", None),
+        Ok(Some(bvec(&[b"\xc3"])))
+    );
+}
+
+#[test]
+fn test_bytes_gmatch() {
+    let fields: Vec<_> = gmatch(b"\x01ab\x02\x01cd\x02", b"\x01([^\x02]*)\x02")
+        .unwrap()
+        .collect::<lsonar::Result<_>>()
+        .unwrap();
+    assert_eq!(fields, vec![bvec(&[b"ab"]), bvec(&[b"cd"])]);
+}
+
+#[test]
+fn test_bytes_gsub() {
+    assert_eq!(
+        gsub(b"a\xe9b\xe9", b"\xe9", b"\xc3\xa9", None),
+        Ok((b"a\xc3\xa9b\xc3\xa9".to_vec(), 2))
+    );
+    assert_eq!(
+        gsub(b"\xff\xfe", b"(.)", b"%1%1", None),
+        Ok((b"\xff\xff\xfe\xfe".to_vec(), 2))
+    );
+    assert_eq!(
+        gsub(
+            b"x\x80y",
+            b"[\x80-\xff]",
+            |captures: &[Capture<&[u8]>]| {
+                format!("\\{}", captures[0].as_bytes().unwrap()[0]).into_bytes()
+            },
+            None
+        ),
+        Ok((b"x\\128y".to_vec(), 1))
+    );
+
+    let mut table = HashMap::new();
+    table.insert(b"\xfe".to_vec(), b"<fe>".to_vec());
+    assert_eq!(
+        gsub(b"\xfe\xfd", b".", &table, None),
+        Ok((b"<fe>\xfd".to_vec(), 2))
+    );
+}
+
+#[test]
+fn test_bytes_pattern_methods() {
+    let pattern = Pattern::new(b"%d+\xff").unwrap();
+
+    assert_eq!(
+        pattern.find_bytes(b"ab12\xff", None),
+        Ok(Some((3, 5, vec![])))
+    );
+    assert_eq!(
+        pattern.match_bytes(b"ab12\xff", None),
+        Ok(Some(bvec(&[b"12\xff"])))
+    );
+    assert_eq!(pattern.gmatch_bytes(b"1\xff2\xff").count(), 2);
+    assert_eq!(
+        pattern.gsub_bytes(b"1\xff2", b"#", None),
+        Ok((b"#2".to_vec(), 1))
+    );
+}
+
+#[test]
+fn test_bytes_try_gsub() {
+    let result = try_gsub(
+        b"\x01a\x02b",
+        b"[\x01\x02]",
+        |captures: &[Capture<&[u8]>]| match captures[0] {
+            Capture::Str(b"\x01") => Ok(b"<".as_slice()),
+            _ => Err("unexpected byte"),
+        },
+        None,
+    );
+    assert_eq!(result, Err(ReplaceError::Replacement("unexpected byte")));
+}
+
+#[test]
+fn test_bytes_gsub_cow_and_into() {
+    let (result, count) = gsub_cow(b"\x00\x01", b"\x02", b"", None).unwrap();
+    assert!(matches!(result, Cow::Borrowed(b"\x00\x01")));
+    assert_eq!(count, 0);
+
+    let mut out = Vec::new();
+    assert_eq!(
+        gsub_into(&mut out, b"a\xffb\xff", b"\xff", b"\xfe", None).unwrap(),
+        2
+    );
+    assert_eq!(out, b"a\xfeb\xfe");
+
+    let err = gsub_into(&mut Vec::new(), b"abc", b"[", b"", None).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let err = err.into_inner().unwrap().downcast::<Error>().unwrap();
+    assert_eq!(err.kind(), ErrorKind::UnfinishedClass);
+}
@@ -1,7 +1,7 @@
-use lsonar::{Error, find};
+use lsonar::{Capture, ErrorKind, Indexing, find, find_plain};
 
-fn svec(items: &[&str]) -> Vec<String> {
-    items.iter().map(|&s| s.to_string()).collect()
+fn svec(items: &[&str]) -> Vec<Capture> {
+    items.iter().map(|&s| Capture::Str(s.to_string())).collect()
 }
 
 #[test]
@@ -49,26 +49,36 @@ fn test_balanced_patterns() {
 
 #[test]
 fn test_find_invalid_pattern() {
-    assert!(matches!(
-        find("abc", "[", None, false),
-        Err(Error::Parser(_))
-    ));
-    assert!(matches!(
-        find("abc", "(", None, false),
-        Err(Error::Parser(_))
-    ));
-    assert!(matches!(
-        find("abc", "*", None, false),
-        Err(Error::Parser(_))
-    ));
-    assert!(matches!(
-        find("abc", "%", None, false),
-        Err(Error::Lexer(_))
-    ));
-    assert!(matches!(
-        find("abc", "%z", None, false),
-        Err(Error::Parser(_)) | Err(Error::Lexer(_))
-    ));
+    let kind = |pattern| find("abc", pattern, None, false).map_err(|err| err.kind());
+    assert_eq!(kind("["), Err(ErrorKind::UnfinishedClass));
+    assert_eq!(kind("("), Err(ErrorKind::UnfinishedCapture));
+    assert_eq!(kind("%"), Err(ErrorKind::EndsWithPercent));
+    assert_eq!(kind("a%1"), Err(ErrorKind::InvalidCaptureIndex(1)));
+    assert_eq!(find("b", "a%1", None, false), Ok(None));
+
+    // Lua reads these as literals and empty ranges, not as errors
+    assert_eq!(find("a*b", "*", None, false), Ok(Some((2, 2, vec![]))));
+    assert_eq!(find("z%q", "%q", None, false), Ok(Some((3, 3, vec![]))));
+    assert_eq!(find("zq", "[%q]", None, false), Ok(Some((2, 2, vec![]))));
+    assert_eq!(find("az", "[z-a]", None, false), Ok(None));
+    assert_eq!(find("a*", "%f[*]*", None, false), Ok(Some((2, 2, vec![]))));
+
+    assert_eq!(
+        find("a:b/c", "%:(%a)%/", None, false),
+        Ok(Some((2, 4, svec(&["b"]))))
+    );
+}
+
+#[test]
+fn test_find_quantifier_after_non_single_item() {
+    // Captures, `%b` and back-references take no quantifier, it matches itself
+    assert_eq!(find("x", "(x)?", None, false), Ok(None));
+    assert_eq!(find("(a)", "%b()*", None, false), Ok(None));
+    assert_eq!(find("aaaa", "(a)%1*", None, false), Ok(None));
+    assert_eq!(
+        find("x?", "(x)?", None, false),
+        Ok(Some((1, 2, svec(&["x"]))))
+    );
 }
 
 #[test]
@@ -92,6 +102,30 @@ fn test_plain_find() {
     );
 }
 
+#[test]
+fn test_plain_find_indexing() {
+    assert_eq!(
+        find_plain("hello world", "world", None, Indexing::OneBased),
+        find("hello world", "world", None, true)
+    );
+    assert_eq!(
+        find_plain("hello world", "world", None, Indexing::ZeroBased),
+        Ok(Some((6, 11, vec![])))
+    );
+    assert_eq!(
+        find_plain("hello world", "o", Some(5), Indexing::ZeroBased),
+        Ok(Some((7, 8, vec![])))
+    );
+    assert_eq!(
+        find_plain("hello world", "", Some(3), Indexing::ZeroBased),
+        Ok(Some((3, 3, vec![])))
+    );
+    assert_eq!(
+        lsonar::bytes::find_plain(b"a.b", b".", None, Indexing::ZeroBased),
+        Ok(Some((1, 2, vec![])))
+    );
+}
+
 #[test]
 fn test_find_with_init() {
     assert_eq!(
@@ -133,3 +167,63 @@ fn test_find_edge_cases() {
     assert_eq!(find("hello", "^", None, false), Ok(Some((1, 0, vec![]))));
     assert_eq!(find("hello", "$", None, false), Ok(Some((6, 5, vec![]))));
 }
+
+#[test]
+fn test_find_position_captures() {
+    assert_eq!(
+        find("hello", "()ll()", None, false),
+        Ok(Some((
+            3,
+            4,
+            vec![Capture::Position(3), Capture::Position(5)]
+        )))
+    );
+    assert_eq!(
+        find("key = value", "(%w+)%s*()=", None, false),
+        Ok(Some((
+            1,
+            5,
+            vec![Capture::Str("key".to_string()), Capture::Position(5)]
+        )))
+    );
+    assert_eq!(
+        find("abc", "()", Some(4), false),
+        Ok(Some((4, 3, vec![Capture::Position(4)])))
+    );
+}
+
+#[test]
+fn test_find_frontier_with_init() {
+    assert_eq!(
+        find("ab cd", "%f[%w]%w+", Some(2), false),
+        Ok(Some((4, 5, vec![])))
+    );
+    assert_eq!(
+        find("ab cd", "%f[%W]", Some(4), false),
+        Ok(Some((6, 5, vec![])))
+    );
+}
+
+#[test]
+fn test_literal_find() {
+    for plain in [true, false] {
+        assert_eq!(find("aaaab", "aab", None, plain), Ok(Some((3, 5, vec![]))));
+        assert_eq!(
+            find("xyz-xyz-xyZ", "xyZ", None, plain),
+            Ok(Some((9, 11, vec![])))
+        );
+        assert_eq!(
+            find("abcabc", "abc", Some(2), plain),
+            Ok(Some((4, 6, vec![])))
+        );
+        assert_eq!(find("ab", "abc", None, plain), Ok(None));
+        assert_eq!(find("abcab", "abc", Some(2), plain), Ok(None));
+    }
+
+    let long = format!("{}needle", "hay ".repeat(10_000));
+    assert_eq!(
+        find(&long, "needle", None, true),
+        Ok(Some((40_001, 40_006, vec![])))
+    );
+    assert_eq!(find(&long, "needles", None, false), Ok(None));
+}
@@ -1,10 +1,10 @@
-use lsonar::{Result, gmatch};
+use lsonar::{Capture, Pattern, Result, gmatch};
 
-fn convert_to_string_vec(items: &[&str]) -> Vec<String> {
-    items.iter().map(|&s| s.to_string()).collect()
+fn convert_to_string_vec(items: &[&str]) -> Vec<Capture> {
+    items.iter().map(|&s| Capture::Str(s.to_string())).collect()
 }
 
-fn collect_gmatch_results(text: &str, pattern: &str) -> Result<Vec<Vec<String>>> {
+fn collect_gmatch_results(text: &str, pattern: &str) -> Result<Vec<Vec<Capture>>> {
     let it = gmatch(text, pattern)?;
     it.collect()
 }
@@ -63,10 +63,10 @@ fn test_single_char_captures() {
 }
 
 #[test]
-fn test_empty_captures() {
+fn test_position_captures() {
     assert_eq!(
         collect_gmatch_results("abc", "()a()"),
-        Ok(vec![convert_to_string_vec(&["", ""])])
+        Ok(vec![vec![Capture::Position(1), Capture::Position(2)]])
     );
 }
 
@@ -136,6 +136,48 @@ fn test_dot_pattern() {
     assert_eq!(result.len(), 11);
 
     for (i, v) in result.into_iter().enumerate() {
-        assert_eq!(v[0], "hello world".chars().nth(i).unwrap().to_string());
+        assert_eq!(
+            v[0],
+            Capture::Str("hello world".chars().nth(i).unwrap().to_string())
+        );
     }
 }
+
+#[test]
+fn test_caret_is_literal() {
+    assert_eq!(collect_gmatch_results("aaa", "^a"), Ok(vec![]));
+    assert_eq!(
+        collect_gmatch_results("^a b ^a", "^a"),
+        Ok(vec![
+            convert_to_string_vec(&["^a"]),
+            convert_to_string_vec(&["^a"])
+        ])
+    );
+    assert_eq!(
+        collect_gmatch_results("^^a^", "^+"),
+        Ok(vec![
+            convert_to_string_vec(&["^^"]),
+            convert_to_string_vec(&["^"])
+        ])
+    );
+    assert_eq!(
+        collect_gmatch_results("^^a", "^*"),
+        Ok(vec![
+            convert_to_string_vec(&["^^"]),
+            convert_to_string_vec(&[""]),
+            convert_to_string_vec(&[""])
+        ])
+    );
+    assert_eq!(
+        collect_gmatch_results("a^", "^-a"),
+        Ok(vec![convert_to_string_vec(&["a"])])
+    );
+
+    // The same compiled pattern still anchors outside of gmatch
+    let pattern = Pattern::new("^a").unwrap();
+    assert_eq!(pattern.find("b^a", None), Ok(None));
+    assert_eq!(
+        pattern.gmatch("b^a").collect::<Result<Vec<_>>>(),
+        Ok(vec![convert_to_string_vec(&["^a"])])
+    );
+}
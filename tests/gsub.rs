@@ -1,10 +1,16 @@
-use lsonar::{Repl, gsub};
-use std::collections::HashMap;
+use lsonar::{
+    Capture, Error, ErrorKind, Match, MatchFn, ReplaceError, Replacer, Value, gsub, gsub_cow,
+    gsub_into, try_gsub,
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
 
 #[test]
 fn test_basic_replacement() {
     assert_eq!(
-        gsub("hello world", "l", Repl::String("L"), None),
+        gsub("hello world", "l", "L", None),
         Ok(("heLLo worLd".to_string(), 3))
     );
 }
@@ -12,7 +18,7 @@ fn test_basic_replacement() {
 #[test]
 fn test_limited_replacement_count() {
     assert_eq!(
-        gsub("hello world", "l", Repl::String("L"), Some(2)),
+        gsub("hello world", "l", "L", Some(2)),
         Ok(("heLLo world".to_string(), 2))
     );
 }
@@ -20,7 +26,7 @@ fn test_limited_replacement_count() {
 #[test]
 fn test_zero_replacement_count() {
     assert_eq!(
-        gsub("hello", ".", Repl::String("x"), Some(0)),
+        gsub("hello", ".", "x", Some(0)),
         Ok(("hello".to_string(), 0))
     );
 }
@@ -28,12 +34,7 @@ fn test_zero_replacement_count() {
 #[test]
 fn test_pattern_with_captures() {
     assert_eq!(
-        gsub(
-            "name=John age=25",
-            "(%w+)=(%w+)",
-            Repl::String("%2 is %1"),
-            None
-        ),
+        gsub("name=John age=25", "(%w+)=(%w+)", "%2 is %1", None),
         Ok(("John is name 25 is age".to_string(), 2))
     );
 }
@@ -41,7 +42,7 @@ fn test_pattern_with_captures() {
 #[test]
 fn test_numeric_pattern() {
     assert_eq!(
-        gsub("hello 123 world 456", "%d+", Repl::String("<number>"), None),
+        gsub("hello 123 world 456", "%d+", "<number>", None),
         Ok(("hello <number> world <number>".to_string(), 2))
     );
 }
@@ -49,7 +50,7 @@ fn test_numeric_pattern() {
 #[test]
 fn test_empty_pattern() {
     assert_eq!(
-        gsub("hello", "", Repl::String("-"), None),
+        gsub("hello", "", "-", None),
         Ok(("-h-e-l-l-o-".to_string(), 6))
     );
 }
@@ -57,7 +58,7 @@ fn test_empty_pattern() {
 #[test]
 fn test_escape_percent_in_replacement() {
     assert_eq!(
-        gsub("hello", "e", Repl::String("%% escaped"), None),
+        gsub("hello", "e", "%% escaped", None),
         Ok(("h% escapedllo".to_string(), 1))
     );
 }
@@ -68,7 +69,7 @@ fn test_complex_pattern_with_captures() {
         gsub(
             "User: John, Age: 25, Email: john@example.com",
             "(User: )(%w+)(, Age: )(%d+)",
-            Repl::String("%1%2%3%4 (adult)"),
+            "%1%2%3%4 (adult)",
             None
         ),
         Ok((
@@ -84,7 +85,7 @@ fn test_function_replacement() {
         gsub(
             "hello world",
             "%w+",
-            Repl::Function(Box::new(|captures: &[&str]| { captures[0].to_uppercase() })),
+            |captures: &[Capture<&str>]| { captures[0].to_string().to_uppercase() },
             None
         ),
         Ok(("HELLO WORLD".to_string(), 2))
@@ -97,13 +98,13 @@ fn test_function_with_captures() {
         gsub(
             "a=1, b=2, c=3",
             "(%w)=(%d)",
-            Repl::Function(Box::new(|captures: &[&str]| {
+            |captures: &[Capture<&str>]| {
                 format!(
                     "{}={}",
                     captures[1],
-                    captures[2].parse::<i32>().unwrap() * 2
+                    captures[2].to_string().parse::<i32>().unwrap() * 2
                 )
-            })),
+            },
             None
         ),
         Ok(("a=2, b=4, c=6".to_string(), 3))
@@ -117,7 +118,7 @@ fn test_table_replacement() {
     table.insert("world".to_string(), "мир".to_string());
 
     assert_eq!(
-        gsub("hello world", "%w+", Repl::Table(&table), None),
+        gsub("hello world", "%w+", &table, None),
         Ok(("привет мир".to_string(), 2))
     );
 }
@@ -128,7 +129,7 @@ fn test_partial_table_replacement() {
     table.insert("hello".to_string(), "привет".to_string());
 
     assert_eq!(
-        gsub("hello world", "%w+", Repl::Table(&table), None),
+        gsub("hello world", "%w+", &table, None),
         Ok(("привет world".to_string(), 2))
     );
 }
@@ -140,23 +141,345 @@ fn test_table_with_captures() {
     table.insert("age".to_string(), "возраст".to_string());
 
     assert_eq!(
-        gsub("name=John age=25", "(%w+)=%w+", Repl::Table(&table), None),
+        gsub("name=John age=25", "(%w+)=%w+", &table, None),
         Ok(("имя возраст".to_string(), 2))
     );
 }
 
 #[test]
-fn test_empty_string() {
+fn test_position_captures() {
     assert_eq!(
-        gsub("", "pattern", Repl::String("repl"), None),
-        Ok(("".to_string(), 0))
+        gsub("hello world", "()o", "%1", None),
+        Ok(("hell5 w8rld".to_string(), 2))
+    );
+    assert_eq!(
+        gsub(
+            "abc",
+            "()%a",
+            |captures: &[Capture<&str>]| {
+                match captures[1] {
+                    Capture::Position(pos) => pos.to_string(),
+                    Capture::Str(_) => unreachable!(),
+                }
+            },
+            None
+        ),
+        Ok(("123".to_string(), 3))
     );
 }
 
+#[test]
+fn test_empty_string() {
+    assert_eq!(gsub("", "pattern", "repl", None), Ok(("".to_string(), 0)));
+}
+
 #[test]
 fn test_pattern_not_found() {
+    assert_eq!(gsub("hello", "x", "y", None), Ok(("hello".to_string(), 0)));
+}
+
+#[test]
+fn test_match_splitting_utf8_characters() {
+    assert_eq!(gsub("héllo", ".", "x", None), Ok(("xxxxxx".to_string(), 6)));
     assert_eq!(
-        gsub("hello", "x", Repl::String("y"), None),
-        Ok(("hello".to_string(), 0))
+        gsub("héllo", "[\u{80}-\u{ff}]", "", None),
+        Ok(("hllo".to_string(), 2))
+    );
+    assert_eq!(
+        gsub("привет", "(.)", "%1", None),
+        Ok(("привет".to_string(), 12))
+    );
+    assert_eq!(
+        gsub(
+            "aé",
+            ".",
+            |captures: &[Capture<&str>]| { captures[0].to_string().len().to_string() },
+            None
+        ),
+        Ok(("133".to_string(), 3))
+    );
+    assert_eq!(
+        gsub("é", "(.)", "%1%1", None).map_err(|err| err.kind()),
+        Err(ErrorKind::InvalidUtf8)
+    );
+}
+
+#[test]
+fn test_literal_pattern_replacement() {
+    assert_eq!(
+        gsub("foo food fo", "foo", "bar", None),
+        Ok(("bar bard fo".to_string(), 2))
+    );
+    assert_eq!(gsub("aaaa", "aa", "b", None), Ok(("bb".to_string(), 2)));
+}
+
+#[test]
+fn test_anchored_pattern_replaces_once() {
+    assert_eq!(gsub("aaa", "^a", "b", None), Ok(("baa".to_string(), 1)));
+    assert_eq!(gsub("xaa", "^a", "b", None), Ok(("xaa".to_string(), 0)));
+    assert_eq!(gsub("abc", "^", ">", None), Ok((">abc".to_string(), 1)));
+}
+
+#[test]
+fn test_replacement_string_escapes() {
+    assert_eq!(
+        gsub("hello world", "o%s", "[%0]", None),
+        Ok(("hell[o ]world".to_string(), 1))
+    );
+    assert_eq!(
+        gsub("abc", "%w", "%1%1", None),
+        Ok(("aabbcc".to_string(), 3))
+    );
+    assert_eq!(
+        gsub("abc", "(b)", "<%0|%1>", None),
+        Ok(("a<b|b>c".to_string(), 1))
+    );
+
+    let error = |pattern: &str, repl: &str| {
+        gsub("abc", pattern, repl, None).map_err(|err| (err.kind(), err.offset()))
+    };
+    assert_eq!(
+        error("b", "x%y"),
+        Err((ErrorKind::InvalidReplacementEscape(b'y'), Some(1)))
+    );
+    assert_eq!(
+        error("b", "100%"),
+        Err((ErrorKind::InvalidReplacementEscape(0), Some(3)))
+    );
+    assert_eq!(
+        error("(b)", "%1%2"),
+        Err((ErrorKind::InvalidCaptureIndex(2), Some(2)))
+    );
+    assert_eq!(
+        error("b", "%2"),
+        Err((ErrorKind::InvalidCaptureIndex(2), Some(0)))
+    );
+    assert_eq!(
+        error("x", "%y").map(|(result, _)| result),
+        Ok("abc".to_string())
+    );
+}
+
+#[test]
+fn test_replacer_implementations() {
+    let mut count = 0;
+    assert_eq!(
+        gsub(
+            "a b c",
+            "%a",
+            |_: &[Capture<&str>]| {
+                count += 1;
+                count.to_string()
+            },
+            None
+        ),
+        Ok(("1 2 3".to_string(), 3))
+    );
+    assert_eq!(count, 3);
+
+    let table = BTreeMap::from([("k", "v")]);
+    assert_eq!(gsub("k=k", "%a", &table, None), Ok(("v=v".to_string(), 2)));
+
+    let replacement = {
+        let text = String::from("x");
+        gsub(&text, "x", String::from("[%0]"), None)
+    };
+    assert_eq!(replacement, Ok(("[x]".to_string(), 1)));
+
+    struct Upper;
+    impl Replacer for Upper {
+        fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> lsonar::Result<()> {
+            dst.extend(m.as_bytes().to_ascii_uppercase());
+            Ok(())
+        }
+    }
+    assert_eq!(
+        gsub("abc def", "%a+", Upper, Some(1)),
+        Ok(("ABC def".to_string(), 1))
+    );
+
+    struct Lookup(Vec<(&'static str, &'static str)>);
+    impl Replacer for Lookup {
+        fn replace_append(&mut self, m: &Match<'_>, dst: &mut Vec<u8>) -> lsonar::Result<()> {
+            let (_, value) = self
+                .0
+                .iter()
+                .find(|(key, _)| key.as_bytes() == m.as_bytes())
+                .ok_or(Error::replacer("unknown variable"))?;
+            dst.extend_from_slice(value.as_bytes());
+            Ok(())
+        }
+    }
+    let vars = || Lookup(vec![("user", "ann")]);
+    assert_eq!(
+        gsub("$user", "%w+", vars(), None),
+        Ok(("$ann".to_string(), 1))
+    );
+    let err = gsub("$user $host", "%w+", vars(), None).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Replacer("unknown variable"));
+    assert_eq!(err.to_string(), "unknown variable");
+}
+
+#[test]
+fn test_replacement_values() {
+    assert_eq!(
+        gsub(
+            "a b c",
+            "%a",
+            |captures: &[Capture<&str>]| { (captures[0] != Capture::Str("b")).then_some("x") },
+            None
+        ),
+        Ok(("x b x".to_string(), 3))
+    );
+    assert_eq!(
+        gsub("a b", "%a", |_: &[Capture<&str>]| false, None),
+        Ok(("a b".to_string(), 2))
+    );
+    assert_eq!(
+        gsub("a b", "%a", |_: &[Capture<&str>]| 42, None),
+        Ok(("42 42".to_string(), 2))
+    );
+    assert_eq!(
+        gsub("x", "x", |_: &[Capture<&str>]| 2.5, None),
+        Ok(("2.5".to_string(), 1))
+    );
+    assert_eq!(
+        gsub("x", "x", |_: &[Capture<&str>]| 3.0, None),
+        Ok(("3.0".to_string(), 1))
+    );
+    assert_eq!(
+        gsub("x", "x", |_: &[Capture<&str>]| 1e100, None),
+        Ok(("1e+100".to_string(), 1))
+    );
+
+    let table = HashMap::from([("a", Some(1)), ("b", None)]);
+    assert_eq!(
+        gsub("a=1, b=2", "(%a)=%d", &table, None),
+        Ok(("1, b=2".to_string(), 2))
+    );
+
+    let error = |value: Value<'static>| {
+        gsub("x", "x", move |_: &[Capture<&str>]| value, None).map_err(|err| err.to_string())
+    };
+    assert_eq!(
+        error(Value::Boolean(true)),
+        Err("invalid replacement value (a boolean)".to_string())
+    );
+    assert_eq!(
+        error(Value::Other("table")),
+        Err("invalid replacement value (a table)".to_string())
+    );
+}
+
+#[test]
+fn test_try_gsub() {
+    let lookup = |captures: &[Capture<&str>]| match captures[1].as_str() {
+        Some("name") => Ok("John"),
+        other => Err(format!("unknown variable {:?}", other)),
+    };
+
+    assert_eq!(
+        try_gsub("Hi $name!", "%$(%w+)", lookup, None),
+        Ok(("Hi John!".to_string(), 1))
+    );
+
+    let mut calls = 0;
+    assert_eq!(
+        try_gsub(
+            "$x $name $y",
+            "%$(%w+)",
+            |captures: &[Capture<&str>]| {
+                calls += 1;
+                lookup(captures)
+            },
+            None
+        ),
+        Err(ReplaceError::Replacement(
+            "unknown variable Some(\"x\")".to_string()
+        ))
+    );
+    assert_eq!(calls, 1);
+
+    assert_eq!(
+        try_gsub("x", "[", lookup, None).map_err(|err| match err {
+            ReplaceError::Pattern(err) => Some(err.kind()),
+            ReplaceError::Replacement(_) => None,
+        }),
+        Err(Some(ErrorKind::UnfinishedClass))
+    );
+    assert_eq!(
+        try_gsub(
+            "x",
+            "x",
+            |_: &[Capture<&str>]| Ok::<_, String>(Value::Boolean(true)),
+            None
+        )
+        .unwrap_err()
+        .to_string(),
+        "invalid replacement value (a boolean)"
+    );
+}
+
+#[test]
+fn test_match_context_replacement() {
+    let text = "one\ntwo three\nfour";
+    assert_eq!(
+        gsub(
+            text,
+            "%a+",
+            MatchFn(|m: &Match| {
+                let at_line_start = m.start() == 0 || m.subject()[m.start() - 1] == b'\n';
+                at_line_start.then(|| format!("{}:{}", m.index(), m.as_str()))
+            }),
+            None
+        ),
+        Ok(("0:one\n1:two three\n3:four".to_string(), 4))
+    );
+
+    let mut ranges = Vec::new();
+    gsub(
+        "k=v",
+        "(%a)=()(%a)",
+        MatchFn(|m: &Match| {
+            ranges.push((m.range(), m.capture_ranges().to_vec(), m.captures().len()));
+            None::<&str>
+        }),
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        ranges,
+        vec![(0..3, vec![Some(0..1), Some(2..2), Some(2..3)], 3)]
+    );
+}
+
+#[test]
+fn test_gsub_cow() {
+    let (result, count) = gsub_cow("hello world", "%d", "#", None).unwrap();
+    assert!(matches!(result, Cow::Borrowed("hello world")));
+    assert_eq!(count, 0);
+
+    let (result, count) = gsub_cow("a1b2", "%d", "#", None).unwrap();
+    assert!(matches!(result, Cow::Owned(_)));
+    assert_eq!((result.as_ref(), count), ("a#b#", 2));
+}
+
+#[test]
+fn test_gsub_into() {
+    let mut out = String::from("> ");
+    assert_eq!(gsub_into(&mut out, "a1b2", "%d", "<%0>", None), Ok(2));
+    assert_eq!(out, "> a<1>b<2>");
+
+    let mut out = String::new();
+    assert_eq!(gsub_into(&mut out, "no digits", "%d", "#", None), Ok(0));
+    assert_eq!(out, "no digits");
+
+    let mut out = String::new();
+    assert_eq!(gsub_into(&mut out, "привет", "(.)", "%1", None), Ok(12));
+    assert_eq!(out, "привет");
+
+    assert_eq!(
+        gsub_into(&mut String::new(), "é", "(.)", "%1%1", None).map_err(|err| err.kind()),
+        Err(ErrorKind::InvalidUtf8)
     );
 }
@@ -1,5 +1,5 @@
 use lsonar::{
-    Error, Result,
+    ErrorKind, Result,
     lexer::{Lexer, token::Token},
 };
 
@@ -27,12 +27,15 @@ fn test_basic_tokens_lexer() -> Result<()> {
         vec![Token::Literal(b'a'), Token::Any, Token::Literal(b'c')]
     );
     assert_eq!(lex_all("()")?, vec![Token::LParen, Token::RParen]);
-    assert_eq!(lex_all("[]")?, vec![Token::LBracket, Token::RBracket]);
+    assert_eq!(
+        lex_all("[a]")?,
+        vec![Token::LBracket, Token::Literal(b'a'), Token::RBracket]
+    );
     assert_eq!(
         lex_all("^$*+?-")?,
         vec![
             Token::Caret,
-            Token::Dollar,
+            Token::Literal(b'$'),
             Token::Star,
             Token::Plus,
             Token::Question,
@@ -42,6 +45,69 @@ fn test_basic_tokens_lexer() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_positional_tokens_lexer() -> Result<()> {
+    assert_eq!(
+        lex_all("^a$")?,
+        vec![Token::Caret, Token::Literal(b'a'), Token::Dollar]
+    );
+    assert_eq!(
+        lex_all("a^b")?,
+        vec![
+            Token::Literal(b'a'),
+            Token::Literal(b'^'),
+            Token::Literal(b'b')
+        ]
+    );
+    assert_eq!(
+        lex_all("$x")?,
+        vec![Token::Literal(b'$'), Token::Literal(b'x')]
+    );
+    assert_eq!(
+        lex_all("(a$)")?,
+        vec![
+            Token::LParen,
+            Token::Literal(b'a'),
+            Token::Literal(b'$'),
+            Token::RParen
+        ]
+    );
+    assert_eq!(
+        lex_all("[]a]")?,
+        vec![
+            Token::LBracket,
+            Token::Literal(b']'),
+            Token::Literal(b'a'),
+            Token::RBracket
+        ]
+    );
+    assert_eq!(
+        lex_all("[^]^]")?,
+        vec![
+            Token::LBracket,
+            Token::Caret,
+            Token::Literal(b']'),
+            Token::Literal(b'^'),
+            Token::RBracket
+        ]
+    );
+    assert_eq!(
+        lex_all("[(.[]")?,
+        vec![
+            Token::LBracket,
+            Token::Literal(b'('),
+            Token::Literal(b'.'),
+            Token::Literal(b'['),
+            Token::RBracket
+        ]
+    );
+    assert_eq!(
+        lex_all("[%a]")?,
+        vec![Token::LBracket, Token::Class(b'a'), Token::RBracket]
+    );
+    Ok(())
+}
+
 #[test]
 fn test_escape_tokens_lexer() -> Result<()> {
     assert_eq!(lex_all("%%")?, vec![Token::EscapedLiteral(b'%')]);
@@ -59,6 +125,26 @@ fn test_escape_tokens_lexer() -> Result<()> {
     assert_eq!(lex_all("%-")?, vec![Token::EscapedLiteral(b'-')]);
     assert_eq!(lex_all("%^")?, vec![Token::EscapedLiteral(b'^')]);
     assert_eq!(lex_all("%$")?, vec![Token::EscapedLiteral(b'$')]);
+    assert_eq!(
+        lex_all("%:%/[%\"]")?,
+        vec![
+            Token::EscapedLiteral(b':'),
+            Token::EscapedLiteral(b'/'),
+            Token::LBracket,
+            Token::EscapedLiteral(b'"'),
+            Token::RBracket
+        ]
+    );
+    assert_eq!(
+        lex_all("%q[%q%1]")?,
+        vec![
+            Token::EscapedLiteral(b'q'),
+            Token::LBracket,
+            Token::EscapedLiteral(b'q'),
+            Token::EscapedLiteral(b'1'),
+            Token::RBracket
+        ]
+    );
     Ok(())
 }
 
@@ -138,9 +224,14 @@ fn test_mixed_tokens_lexer() -> Result<()> {
 
 #[test]
 fn test_lexer_throw_errors() {
-    assert!(matches!(lex_all("%"), Err(Error::Lexer(_))));
-    assert!(matches!(lex_all("%q"), Err(Error::Lexer(_))));
-    assert!(matches!(lex_all("abc%"), Err(Error::Lexer(_))));
+    let error = |pattern| lex_all(pattern).map_err(|err| (err.kind(), err.offset()));
+    assert_eq!(error("%"), Err((ErrorKind::EndsWithPercent, Some(0))));
+    assert_eq!(error("abc%"), Err((ErrorKind::EndsWithPercent, Some(3))));
+    assert_eq!(error("x[a%"), Err((ErrorKind::UnfinishedClass, Some(1))));
+    assert_eq!(
+        error("x%bx"),
+        Err((ErrorKind::MissingBalancedArguments, Some(1)))
+    );
 }
 
 #[test]
@@ -1,7 +1,7 @@
-use lsonar::r#match;
+use lsonar::{Capture, r#match};
 
-fn convert_to_string_vec(items: &[&str]) -> Vec<String> {
-    items.iter().map(|&s| s.to_string()).collect()
+fn convert_to_string_vec(items: &[&str]) -> Vec<Capture> {
+    items.iter().map(|&s| Capture::Str(s.to_string())).collect()
 }
 
 #[test]
@@ -58,9 +58,28 @@ fn test_combined_pattern_captures() {
 }
 
 #[test]
-fn test_empty_captures() {
+fn test_position_captures() {
     assert_eq!(
         r#match("hello", "(h)()ello", None),
+        Ok(Some(vec![
+            Capture::Str("h".to_string()),
+            Capture::Position(2)
+        ]))
+    );
+    assert_eq!(
+        r#match("hello", "()ll", None),
+        Ok(Some(vec![Capture::Position(3)]))
+    );
+    assert_eq!(
+        r#match("hello", "()$", None),
+        Ok(Some(vec![Capture::Position(6)]))
+    );
+}
+
+#[test]
+fn test_empty_string_captures() {
+    assert_eq!(
+        r#match("hello", "(h)(x*)ello", None),
         Ok(Some(convert_to_string_vec(&["h", ""])))
     );
 }
@@ -1,11 +1,11 @@
 use lsonar::charset::CharSet;
-use lsonar::{AstNode, Error, LUA_MAXCAPTURES, Parser, Quantifier, Result};
+use lsonar::{AstNode, Dialect, ErrorKind, LUA_MAXCAPTURES, Parser, Quantifier, Result};
 
 fn parse_ok(pattern: &str) -> Vec<AstNode> {
     Parser::new(pattern)
         .expect("Parser::new failed")
         .parse()
-        .expect(&format!("Parser failed for pattern: {}", pattern))
+        .unwrap_or_else(|_| panic!("Parser failed for pattern: {}", pattern))
 }
 
 fn parse_err(pattern: &str) -> Result<Vec<AstNode>> {
@@ -26,10 +26,10 @@ fn make_set(bytes: &[u8], ranges: &[(u8, u8)], classes: &[u8], negated: bool) ->
         set.add_byte(b);
     }
     for &(s, e) in ranges {
-        set.add_range(s, e).unwrap();
+        set.add_range(s, e);
     }
     for &c in classes {
-        set.add_class(c).unwrap();
+        set.add_class(c, Dialect::default()).unwrap();
     }
     if negated {
         set.invert();
@@ -141,17 +141,13 @@ fn test_quantifiers_parser() {
 
 #[test]
 fn test_sets_parser() {
-    assert_eq!(
-        parse_ok("[]"),
-        vec![AstNode::Set(make_set(&[], &[], &[], false))]
-    );
     assert_eq!(
         parse_ok("[abc]"),
-        vec![AstNode::Set(make_set(&[b'a', b'b', b'c'], &[], &[], false))]
+        vec![AstNode::Set(make_set(b"abc", &[], &[], false))]
     );
     assert_eq!(
         parse_ok("[^abc]"),
-        vec![AstNode::Set(make_set(&[b'a', b'b', b'c'], &[], &[], true))]
+        vec![AstNode::Set(make_set(b"abc", &[], &[], true))]
     );
     assert_eq!(
         parse_ok("[a-c]"),
@@ -163,38 +159,74 @@ fn test_sets_parser() {
     );
     assert_eq!(
         parse_ok("[a.^$]"),
-        vec![AstNode::Set(make_set(
-            &[b'a', b'.', b'^', b'$'],
-            &[],
-            &[],
-            false
-        ))]
+        vec![AstNode::Set(make_set(b"a.^$", &[], &[], false))]
     );
     assert_eq!(
         parse_ok("[%a]"),
-        vec![AstNode::Set(make_set(&[], &[], &[b'a'], false))]
+        vec![AstNode::Set(make_set(&[], &[], b"a", false))]
     );
     assert_eq!(
         parse_ok("[%%]"),
-        vec![AstNode::Set(make_set(&[b'%'], &[], &[], false))]
+        vec![AstNode::Set(make_set(b"%", &[], &[], false))]
     );
     assert_eq!(
         parse_ok("[-abc]"),
-        vec![AstNode::Set(make_set(
-            &[b'-', b'a', b'b', b'c'],
-            &[],
-            &[],
-            false
-        ))]
+        vec![AstNode::Set(make_set(b"-abc", &[], &[], false))]
     );
     assert_eq!(
         parse_ok("[abc-]"),
-        vec![AstNode::Set(make_set(
-            &[b'a', b'b', b'c', b'-'],
-            &[],
-            &[],
-            false
-        ))]
+        vec![AstNode::Set(make_set(b"abc-", &[], &[], false))]
+    );
+}
+
+#[test]
+fn test_positional_anchors_parser() {
+    assert_eq!(
+        parse_ok("a^b"),
+        vec![
+            AstNode::Literal(b'a'),
+            AstNode::Literal(b'^'),
+            AstNode::Literal(b'b')
+        ]
+    );
+    assert_eq!(
+        parse_ok("$x"),
+        vec![AstNode::Literal(b'$'), AstNode::Literal(b'x')]
+    );
+    assert_eq!(
+        parse_ok("$+"),
+        vec![quantified(AstNode::Literal(b'$'), Quantifier::Plus)]
+    );
+    assert_eq!(
+        parse_ok("(^a$)"),
+        vec![AstNode::Capture {
+            index: 1,
+            inner: vec![
+                AstNode::Literal(b'^'),
+                AstNode::Literal(b'a'),
+                AstNode::Literal(b'$')
+            ]
+        }]
+    );
+    assert_eq!(
+        parse_ok("[]abc]"),
+        vec![AstNode::Set(make_set(b"]abc", &[], &[], false))]
+    );
+    assert_eq!(
+        parse_ok("[^]]"),
+        vec![AstNode::Set(make_set(b"]", &[], &[], true))]
+    );
+    assert_eq!(
+        parse_ok("[]-a]"),
+        vec![AstNode::Set(make_set(&[], &[(b']', b'a')], &[], false))]
+    );
+    assert_eq!(
+        parse_ok("[^^]"),
+        vec![AstNode::Set(make_set(b"^", &[], &[], true))]
+    );
+    assert_eq!(
+        parse_ok("[%d%s]"),
+        vec![AstNode::Set(make_set(&[], &[], b"ds", false))]
     );
 }
 
@@ -203,7 +235,7 @@ fn test_set_quantifier_parser() {
     assert_eq!(
         parse_ok("[abc]*"),
         vec![quantified(
-            AstNode::Set(make_set(&[b'a', b'b', b'c'], &[], &[], false)),
+            AstNode::Set(make_set(b"abc", &[], &[], false)),
             Quantifier::Star
         )]
     );
@@ -251,13 +283,13 @@ fn test_captures_parser() {
     );
     assert_eq!(
         parse_ok("(a)?"),
-        vec![quantified(
+        vec![
             AstNode::Capture {
                 index: 1,
                 inner: vec![AstNode::Literal(b'a')]
             },
-            Quantifier::Question
-        )]
+            AstNode::Literal(b'?')
+        ]
     );
     assert_eq!(
         parse_ok("a?b"),
@@ -280,7 +312,7 @@ fn test_balanced_frontier_parser() {
     assert_eq!(parse_ok("%b()"), vec![AstNode::Balanced(b'(', b')')]);
     assert_eq!(
         parse_ok("%f[ac]"),
-        vec![AstNode::Frontier(make_set(&[b'a', b'c'], &[], &[], false))]
+        vec![AstNode::Frontier(make_set(b"ac", &[], &[], false))]
     );
 }
 
@@ -308,36 +340,63 @@ fn test_escaped_rparen_rbracket_without_panic() {
 
 #[test]
 fn test_throw_parser_errors() {
-    assert!(
-        matches!(parse_err("("), Err(Error::Parser(s)) if s.contains("malformed pattern (unexpected end, expected RParen)"))
-    );
-    assert!(matches!(parse_err(")"), Err(Error::Parser(s)) if s.contains("unexpected ')'")));
-    assert!(matches!(parse_err("]"), Err(Error::Parser(s)) if s.contains("unexpected ']'")));
-    assert!(
-        matches!(parse_err("["), Err(Error::Parser(s)) if s.contains("unfinished character class"))
-    );
-    assert!(matches!(parse_err("*"), Err(Error::Parser(s)) if s.contains("must follow an item")));
-    assert!(matches!(parse_err("^*"), Err(Error::Parser(s)) if s.contains("cannot be quantified")));
-    assert!(matches!(parse_err("$+"), Err(Error::Parser(s)) if s.contains("cannot be quantified")));
-    assert!(matches!(parse_err("%b"), Err(Error::Lexer(s)) if s.contains("needs two characters")));
-    assert!(matches!(parse_err("%bx"), Err(Error::Lexer(s)) if s.contains("needs two characters")));
-    assert!(matches!(parse_err("%f"), Err(Error::Parser(s)) if s.contains("missing '[' after %f")));
-    assert!(
-        matches!(parse_err("%fa"), Err(Error::Parser(s)) if s.contains("missing '[' after %f"))
-    );
-    assert!(
-        matches!(parse_err("%f["), Err(Error::Parser(s)) if s.contains("unfinished character class"))
-    );
-    assert!(
-        matches!(parse_err("%f[a"), Err(Error::Parser(s)) if s.contains("unfinished character class"))
-    );
-    assert!(matches!(parse_err("%z"), Err(Error::Lexer(_))));
+    let error = |pattern| parse_err(pattern).map_err(|err| (err.kind(), err.offset()));
+    assert_eq!(error("("), Err((ErrorKind::UnfinishedCapture, Some(0))));
+    assert_eq!(
+        error("a(b(c)"),
+        Err((ErrorKind::UnfinishedCapture, Some(1)))
+    );
+    assert_eq!(error(")"), Err((ErrorKind::UnmatchedCaptureClose, Some(0))));
+    assert_eq!(error("["), Err((ErrorKind::UnfinishedClass, Some(0))));
+    assert_eq!(error("ab[]"), Err((ErrorKind::UnfinishedClass, Some(2))));
+    assert_eq!(
+        error("%b"),
+        Err((ErrorKind::MissingBalancedArguments, Some(0)))
+    );
+    assert_eq!(
+        error("%bx"),
+        Err((ErrorKind::MissingBalancedArguments, Some(0)))
+    );
+    assert_eq!(
+        error("%f"),
+        Err((ErrorKind::MissingBracketAfterFrontier, Some(0)))
+    );
+    assert_eq!(
+        error("a%fa"),
+        Err((ErrorKind::MissingBracketAfterFrontier, Some(1)))
+    );
+    assert_eq!(error("%f["), Err((ErrorKind::UnfinishedClass, Some(2))));
+    assert_eq!(error("%f[a"), Err((ErrorKind::UnfinishedClass, Some(2))));
 
-    assert_eq!(parse_ok("%1"), vec![AstNode::CaptureRef(1)]);
+    assert_eq!(
+        parse_ok("%1"),
+        vec![AstNode::CaptureRef {
+            index: 1,
+            offset: 0
+        }]
+    );
+    assert_eq!(parse_ok("]"), vec![AstNode::Literal(b']')]);
+    assert_eq!(
+        parse_ok("^*"),
+        vec![AstNode::AnchorStart, AstNode::Literal(b'*')]
+    );
+    assert_eq!(
+        parse_ok("*+"),
+        vec![AstNode::Quantified {
+            item: Box::new(AstNode::Literal(b'*')),
+            quantifier: Quantifier::Plus
+        }]
+    );
+    assert_eq!(parse_ok("%q"), vec![AstNode::Literal(b'q')]);
+    assert_eq!(
+        parse_ok("[a-z9-0]"),
+        vec![AstNode::Set(make_set(&[], &[(b'a', b'z')], &[], false))]
+    );
 
     let too_many_captures = "()".repeat(LUA_MAXCAPTURES + 1);
-    assert!(
-        matches!(parse_err(&too_many_captures), Err(Error::Parser(s)) if s.contains("too many captures"))
+    assert_eq!(
+        error(&too_many_captures),
+        Err((ErrorKind::TooManyCaptures, Some(2 * LUA_MAXCAPTURES)))
     );
 }
 
@@ -345,19 +404,19 @@ fn test_throw_parser_errors() {
 fn test_special_byte_edge_cases_parser() {
     assert_eq!(
         parse_ok("[%%]"),
-        vec![AstNode::Set(make_set(&[b'%'], &[], &[], false))]
+        vec![AstNode::Set(make_set(b"%", &[], &[], false))]
     );
     assert_eq!(
         parse_ok("[%-]"),
-        vec![AstNode::Set(make_set(&[b'-'], &[], &[], false))]
+        vec![AstNode::Set(make_set(b"-", &[], &[], false))]
     );
     assert_eq!(
         parse_ok("[%]]"),
-        vec![AstNode::Set(make_set(&[b']'], &[], &[], false))]
+        vec![AstNode::Set(make_set(b"]", &[], &[], false))]
     );
     assert_eq!(
         parse_ok("[%[]"),
-        vec![AstNode::Set(make_set(&[b'['], &[], &[], false))]
+        vec![AstNode::Set(make_set(b"[", &[], &[], false))]
     );
 
     assert_eq!(
@@ -369,7 +428,7 @@ fn test_special_byte_edge_cases_parser() {
     );
     assert_eq!(
         parse_ok("[%[]"),
-        vec![AstNode::Set(make_set(&[b'['], &[], &[], false))]
+        vec![AstNode::Set(make_set(b"[", &[], &[], false))]
     );
 }
 
@@ -377,25 +436,35 @@ fn test_special_byte_edge_cases_parser() {
 fn test_nested_complex_patterns_parser() {
     assert_eq!(
         parse_ok("((a+)?(b*))+"),
-        vec![quantified(
+        vec![
             AstNode::Capture {
                 index: 1,
                 inner: vec![
-                    quantified(
-                        AstNode::Capture {
-                            index: 2,
-                            inner: vec![quantified(AstNode::Literal(b'a'), Quantifier::Plus)]
-                        },
-                        Quantifier::Question
-                    ),
+                    AstNode::Capture {
+                        index: 2,
+                        inner: vec![quantified(AstNode::Literal(b'a'), Quantifier::Plus)]
+                    },
+                    AstNode::Literal(b'?'),
                     AstNode::Capture {
                         index: 3,
                         inner: vec![quantified(AstNode::Literal(b'b'), Quantifier::Star)]
                     }
                 ]
             },
-            Quantifier::Plus
-        )]
+            AstNode::Literal(b'+')
+        ]
+    );
+    assert_eq!(
+        parse_ok("%b()*%1-"),
+        vec![
+            AstNode::Balanced(b'(', b')'),
+            AstNode::Literal(b'*'),
+            AstNode::CaptureRef {
+                index: 1,
+                offset: 5
+            },
+            AstNode::Literal(b'-')
+        ]
     );
 
     assert_eq!(
@@ -403,7 +472,7 @@ fn test_nested_complex_patterns_parser() {
         vec![AstNode::Capture {
             index: 1,
             inner: vec![
-                AstNode::Frontier(make_set(&[], &[], &[b'a'], false)),
+                AstNode::Frontier(make_set(&[], &[], b"a", false)),
                 quantified(AstNode::Class(b'w', false), Quantifier::Plus)
             ]
         }]
@@ -412,21 +481,21 @@ fn test_nested_complex_patterns_parser() {
 
 #[test]
 fn test_real_world_patterns_parser() {
-    assert!(parse_ok("https?://[%w%.%-%+]+%.%w+").len() > 0);
+    assert!(!parse_ok("https?://[%w%.%-%+]+%.%w+").is_empty());
 
-    assert!(parse_ok("^[%w%.%+%-]+@[%w%.%+%-]+%.%w+$").len() > 0);
+    assert!(!parse_ok("^[%w%.%+%-]+@[%w%.%+%-]+%.%w+$").is_empty());
 
-    assert!(parse_ok("(%d%d?)/(%d%d?)/(%d%d%d%d)").len() > 0);
+    assert!(!parse_ok("(%d%d?)/(%d%d?)/(%d%d%d%d)").is_empty());
 
-    assert!(parse_ok("(%d+)%.(%d+)%.(%d+)%.(%d+)").len() > 0);
+    assert!(!parse_ok("(%d+)%.(%d+)%.(%d+)%.(%d+)").is_empty());
 
-    assert!(parse_ok("\"([^\"]+)\":%s*\"([^\"]*)\"").len() > 0);
+    assert!(!parse_ok("\"([^\"]+)\":%s*\"([^\"]*)\"").is_empty());
 }
 
 #[test]
 fn test_special_lua_pattern_features_parser() {
-    assert!(parse_ok("%1").len() > 0);
-    assert!(parse_ok("(.)%1").len() > 0);
-    assert!(parse_ok("%b{}").len() > 0);
-    assert!(parse_ok("%f[%a]").len() > 0);
+    assert!(!parse_ok("%1").is_empty());
+    assert!(!parse_ok("(.)%1").is_empty());
+    assert!(!parse_ok("%b{}").is_empty());
+    assert!(!parse_ok("%f[%a]").is_empty());
 }
@@ -0,0 +1,413 @@
+use lsonar::{
+    Capture, CharSet, Dialect, ErrorKind, Indexing, LUA_MAXCAPTURES, MatchOptions, Pattern,
+};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+fn svec(items: &[&str]) -> Vec<Capture> {
+    items.iter().map(|&s| Capture::Str(s.to_string())).collect()
+}
+
+#[test]
+fn test_pattern_construction() {
+    assert!(Pattern::new("(%w+)=(%w+)").is_ok());
+    assert!("%d+".parse::<Pattern>().is_ok());
+    assert!(Pattern::try_from("[a-z]").is_ok());
+
+    assert_eq!(Pattern::new("(a)()(b(c))").unwrap().capture_count(), 4);
+    assert_eq!(Pattern::new("abc").unwrap().capture_count(), 0);
+
+    let error = |result: lsonar::Result<Pattern>| result.map_err(|err| (err.kind(), err.offset()));
+    assert_eq!(
+        error(Pattern::new("[a")),
+        Err((ErrorKind::UnfinishedClass, Some(0)))
+    );
+    assert_eq!(
+        error("%".parse()),
+        Err((ErrorKind::EndsWithPercent, Some(0)))
+    );
+
+    // Back-references are checked when matching reaches them, as in Lua
+    let pattern = Pattern::new("(a)%2").unwrap();
+    assert_eq!(pattern.find("b", None), Ok(None));
+    assert_eq!(
+        pattern
+            .find("a", None)
+            .map_err(|err| (err.kind(), err.offset())),
+        Err((ErrorKind::InvalidCaptureIndex(2), Some(3)))
+    );
+}
+
+#[test]
+fn test_pattern_reuse() {
+    let pattern = Pattern::new("(%w+)=(%w+)").unwrap();
+
+    for (line, key, value) in [("a=1", "a", "1"), ("name=John", "name", "John")] {
+        assert_eq!(pattern.r#match(line, None), Ok(Some(svec(&[key, value]))));
+    }
+
+    assert_eq!(
+        pattern.find("x name=John", None),
+        Ok(Some((3, 11, svec(&["name", "John"]))))
+    );
+    assert_eq!(pattern.find("no pairs here", None), Ok(None));
+}
+
+#[test]
+fn test_pattern_gmatch_and_gsub() {
+    let pattern = Pattern::new("%d+").unwrap();
+
+    let numbers: Vec<_> = pattern
+        .gmatch("1 22 333")
+        .collect::<lsonar::Result<_>>()
+        .unwrap();
+    assert_eq!(numbers, vec![svec(&["1"]), svec(&["22"]), svec(&["333"])]);
+
+    assert_eq!(
+        pattern.gsub("1 22 333", "<n>", Some(2)),
+        Ok(("<n> <n> 333".to_string(), 2))
+    );
+}
+
+#[test]
+fn test_pattern_step_budget() {
+    let hostile = " a".repeat(200);
+    let options = MatchOptions {
+        max_steps: Some(10_000),
+        ..MatchOptions::default()
+    };
+    let pattern = Pattern::new("(.-)%s*(.-)%s*(.-)x")
+        .unwrap()
+        .with_options(options.clone());
+
+    let too_complex = ErrorKind::PatternTooComplex;
+    assert_eq!(
+        pattern.find(&hostile, None).map_err(|err| err.kind()),
+        Err(too_complex)
+    );
+    assert_eq!(
+        pattern.gsub(&hostile, "", None).map_err(|err| err.kind()),
+        Err(too_complex)
+    );
+    let mut iter = pattern.gmatch(&hostile);
+    assert_eq!(
+        iter.next().map(|result| result.map_err(|err| err.kind())),
+        Some(Err(too_complex))
+    );
+    assert_eq!(iter.next(), None);
+
+    let simple = Pattern::new("(%a+)").unwrap().with_options(options);
+    assert_eq!(
+        simple.gsub(&hostile, "b", None),
+        Ok((" b".repeat(200), 200))
+    );
+}
+
+#[test]
+fn test_pattern_deadline_and_cancel() {
+    let text = "a".repeat(2_000);
+
+    let expired = Pattern::new("a-b").unwrap().with_options(MatchOptions {
+        deadline: Some(Instant::now()),
+        ..MatchOptions::default()
+    });
+    assert_eq!(
+        expired.find(&text, None).map_err(|err| err.kind()),
+        Err(ErrorKind::PatternTooComplex)
+    );
+
+    let cancel = Arc::new(AtomicBool::new(true));
+    let cancelled = Pattern::new("a-b").unwrap().with_options(MatchOptions {
+        cancel: Some(cancel.clone()),
+        ..MatchOptions::default()
+    });
+    assert_eq!(
+        cancelled.find(&text, None).map_err(|err| err.kind()),
+        Err(ErrorKind::Cancelled)
+    );
+
+    cancel.store(false, Ordering::Relaxed);
+    assert_eq!(cancelled.find(&text, None), Ok(None));
+
+    // Every match of `%a` is cheap, the flag and the deadline still stop the whole call
+    let words = "a ".repeat(10_000);
+    let cheap = Pattern::new("%a").unwrap().with_options(MatchOptions {
+        cancel: Some(cancel.clone()),
+        ..MatchOptions::default()
+    });
+    assert_eq!(
+        cheap.gsub(&words, "b", None),
+        Ok(("b ".repeat(10_000), 10_000))
+    );
+    cancel.store(true, Ordering::Relaxed);
+    assert_eq!(
+        cheap.gsub(&words, "b", None).map_err(|err| err.kind()),
+        Err(ErrorKind::Cancelled)
+    );
+    let mut matches = cheap.gmatch(&words);
+    assert_eq!(
+        matches.next().map(|m| m.map_err(|err| err.kind())),
+        Some(Err(ErrorKind::Cancelled))
+    );
+    assert!(matches.next().is_none());
+
+    let expired = cheap.with_options(MatchOptions {
+        deadline: Some(Instant::now()),
+        ..MatchOptions::default()
+    });
+    assert_eq!(
+        expired.gsub(&words, "b", None).map_err(|err| err.kind()),
+        Err(ErrorKind::PatternTooComplex)
+    );
+    assert_eq!(
+        expired
+            .gmatch(&words)
+            .map(|m| m.map_err(|err| err.kind()))
+            .collect::<Vec<_>>(),
+        vec![Err(ErrorKind::PatternTooComplex)]
+    );
+}
+
+#[test]
+fn test_pattern_error_trait() {
+    let err = Pattern::new("a[b").unwrap_err();
+    assert_eq!(err.to_string(), "malformed pattern (missing ']')");
+
+    let boxed: Box<dyn std::error::Error> = Box::new(err);
+    assert_eq!(boxed.to_string(), "malformed pattern (missing ']')");
+}
+
+#[test]
+fn test_pattern_lua_error_messages() {
+    for (pattern, message) in [
+        ("%", "malformed pattern (ends with '%')"),
+        ("[a", "malformed pattern (missing ']')"),
+        ("[a%", "malformed pattern (missing ']')"),
+        ("[]", "malformed pattern (missing ']')"),
+        ("%f", "missing '[' after '%f' in pattern"),
+        ("%fx", "missing '[' after '%f' in pattern"),
+        ("%b", "malformed pattern (missing arguments to '%b')"),
+        ("%bx", "malformed pattern (missing arguments to '%b')"),
+        ("(a", "unfinished capture"),
+        ("a)", "invalid pattern capture"),
+    ] {
+        assert_eq!(
+            Pattern::new(pattern).unwrap_err().to_string(),
+            message,
+            "pattern {:?}",
+            pattern
+        );
+    }
+
+    for (pattern, message) in [
+        ("%1", "invalid capture index %1"),
+        ("(a)%0", "invalid capture index %0"),
+        ("(a%1)", "invalid capture index %1"),
+    ] {
+        assert_eq!(
+            Pattern::new(pattern)
+                .unwrap()
+                .find("a", None)
+                .unwrap_err()
+                .to_string(),
+            message,
+            "pattern {:?}",
+            pattern
+        );
+    }
+
+    let too_many_captures = "()".repeat(LUA_MAXCAPTURES + 1);
+    assert_eq!(
+        Pattern::new(&too_many_captures).unwrap_err().to_string(),
+        "too many captures"
+    );
+}
+
+#[test]
+fn test_pattern_indexing() {
+    let one_based = Pattern::new("()(%d+)").unwrap().with_options(MatchOptions {
+        indexing: Indexing::OneBased,
+        ..MatchOptions::default()
+    });
+    let zero_based = Pattern::new("()(%d+)").unwrap().with_options(MatchOptions {
+        indexing: Indexing::ZeroBased,
+        ..MatchOptions::default()
+    });
+
+    let text = "ab 12 34";
+    assert_eq!(
+        one_based.find(text, None),
+        Ok(Some((
+            4,
+            5,
+            vec![Capture::Position(4), Capture::Str("12".to_string())]
+        )))
+    );
+    assert_eq!(
+        zero_based.find(text, None),
+        Ok(Some((
+            3,
+            5,
+            vec![Capture::Position(3), Capture::Str("12".to_string())]
+        )))
+    );
+
+    assert_eq!(
+        one_based.r#match(text, Some(6)),
+        Ok(Some(vec![
+            Capture::Position(7),
+            Capture::Str("34".to_string())
+        ]))
+    );
+    assert_eq!(
+        zero_based.r#match(text, Some(6)),
+        Ok(Some(vec![
+            Capture::Position(6),
+            Capture::Str("34".to_string())
+        ]))
+    );
+    assert_eq!(
+        zero_based.r#match(text, Some(-2)),
+        Ok(Some(vec![
+            Capture::Position(6),
+            Capture::Str("34".to_string())
+        ]))
+    );
+}
+
+#[test]
+fn test_pattern_dialect_classes() {
+    let lua51 = Pattern::with_dialect("%z+", Dialect::Lua51).unwrap();
+    assert_eq!(lua51.find("a\0\0b", None), Ok(Some((2, 3, vec![]))));
+    let lua52 = Pattern::with_dialect("[%Z]+", Dialect::Lua52).unwrap();
+    assert_eq!(lua52.find("\0ab\0", None), Ok(Some((2, 3, vec![]))));
+
+    // A class letter the version does not know matches itself
+    let lua53 = Pattern::with_dialect("a%z", Dialect::Lua53).unwrap();
+    assert_eq!(lua53.find("aza\0", None), Ok(Some((3, 4, vec![]))));
+    let lua54 = Pattern::with_dialect("a%z", Dialect::Lua54).unwrap();
+    assert_eq!(lua54.find("a\0az", None), Ok(Some((3, 4, vec![]))));
+    let lua51 = Pattern::with_dialect("[%g]", Dialect::Lua51).unwrap();
+    assert_eq!(lua51.find("!g", None), Ok(Some((2, 2, vec![]))));
+    let lua51 = Pattern::with_dialect("%G", Dialect::Lua51).unwrap();
+    assert_eq!(lua51.find("gG", None), Ok(Some((2, 2, vec![]))));
+
+    let mut set = CharSet::new();
+    assert_eq!(
+        set.add_class(b'z', Dialect::Lua54)
+            .map_err(|err| err.kind()),
+        Err(ErrorKind::InvalidClass(b'z'))
+    );
+    assert_eq!(set.add_class(b'Z', Dialect::Lua52), Ok(()));
+    assert!(set.contains(b'a') && !set.contains(0));
+    assert!(Pattern::with_dialect("%g", Dialect::Lua54).is_ok());
+    assert_eq!(Pattern::new("%g").unwrap().dialect(), Dialect::Lua53);
+}
+
+#[test]
+fn test_pattern_dialect_empty_matches() {
+    let gsub = |pattern: &str, text: &str, dialect| {
+        Pattern::with_dialect(pattern, dialect)
+            .unwrap()
+            .gsub(text, "-", None)
+    };
+    assert_eq!(
+        gsub("%w*", "abc", Dialect::Lua53),
+        Ok(("--".to_string(), 2))
+    );
+    assert_eq!(gsub("%w*", "abc", Dialect::Lua54), Ok(("-".to_string(), 1)));
+    assert_eq!(
+        gsub("%a*", "ab cd", Dialect::Lua51),
+        Ok(("-- --".to_string(), 4))
+    );
+    assert_eq!(
+        gsub("%a*", "ab cd", Dialect::Lua54),
+        Ok(("- -".to_string(), 2))
+    );
+
+    let gmatch = |pattern: &str, text: &str, dialect| -> Vec<Vec<Capture>> {
+        Pattern::with_dialect(pattern, dialect)
+            .unwrap()
+            .gmatch(text)
+            .collect::<lsonar::Result<_>>()
+            .unwrap()
+    };
+    assert_eq!(
+        gmatch("%a*", "ab cd", Dialect::Lua53),
+        vec![svec(&["ab"]), svec(&[""]), svec(&["cd"]), svec(&[""])]
+    );
+    assert_eq!(
+        gmatch("%a*", "ab cd", Dialect::Lua54),
+        vec![svec(&["ab"]), svec(&["cd"])]
+    );
+}
+
+#[test]
+fn test_pattern_dialect_replacement_escape() {
+    let gsub = |dialect| {
+        Pattern::with_dialect("b", dialect)
+            .unwrap()
+            .gsub("abc", "%x", None)
+            .map_err(|err| err.kind())
+    };
+    assert_eq!(gsub(Dialect::Lua51), Ok(("axc".to_string(), 1)));
+    assert_eq!(
+        gsub(Dialect::Lua52),
+        Err(ErrorKind::InvalidReplacementEscape(b'x'))
+    );
+}
+
+#[test]
+fn test_pattern_dialect_number_replacement() {
+    let gsub = |dialect, value: f64| {
+        Pattern::with_dialect("x", dialect)
+            .unwrap()
+            .gsub("x", move |_: &[Capture<&str>]| value, None)
+            .unwrap()
+            .0
+    };
+    assert_eq!(gsub(Dialect::Lua51, 3.0), "3");
+    assert_eq!(gsub(Dialect::Lua53, 3.0), "3.0");
+    assert_eq!(gsub(Dialect::Lua54, 0.1), "0.1");
+    assert_eq!(gsub(Dialect::Lua52, 1.0 / 3.0), "0.33333333333333");
+}
+
+#[test]
+fn test_pattern_gmatch_init() {
+    let options = MatchOptions {
+        indexing: Indexing::OneBased,
+        ..MatchOptions::default()
+    };
+    let collect = |dialect, init| -> Vec<Vec<Capture>> {
+        Pattern::with_dialect("%d+", dialect)
+            .unwrap()
+            .with_options(options.clone())
+            .gmatch_from("1 22 333", init)
+            .collect::<lsonar::Result<_>>()
+            .unwrap()
+    };
+
+    assert_eq!(
+        collect(Dialect::Lua54, Some(4)),
+        vec![svec(&["2"]), svec(&["333"])]
+    );
+    assert_eq!(collect(Dialect::Lua54, Some(-2)), vec![svec(&["33"])]);
+    assert_eq!(collect(Dialect::Lua53, Some(-2)).len(), 3);
+
+    // An `init` past the end finds nothing, not even an empty match
+    let empty = Pattern::with_dialect("", Dialect::Lua54).unwrap();
+    assert_eq!(empty.gmatch_from("abc", Some(10)).count(), 0);
+    assert_eq!(empty.gmatch_from("abc", Some(4)).count(), 1);
+    assert_eq!(
+        Pattern::with_dialect("", Dialect::Lua53)
+            .unwrap()
+            .gmatch_from("abc", Some(10))
+            .count(),
+        4
+    );
+}
@@ -1,4 +1,6 @@
-use lsonar::{LUA_MAXCAPTURES, Parser, Result, engine::find_first_match};
+#![allow(clippy::type_complexity)]
+
+use lsonar::{ErrorKind, LUA_MAXCAPTURES, Parser, Result, engine::find_first_match};
 use std::ops::Range;
 
 fn find(
@@ -106,6 +108,22 @@ fn test_anchor_match_engine() {
     assert_match("^$", "", 0..0, &[]);
 }
 
+#[test]
+fn test_positional_anchor_engine() {
+    assert_match("a^b", "xa^b", 1..4, &[]);
+    assert_match("$x", "a$x", 1..3, &[]);
+    assert_match("a$b$", "a$b", 0..3, &[]);
+    assert_match("[]abc]+", "x]ab]", 1..5, &[]);
+    assert_match("[^]]+", "]]ab]", 2..4, &[]);
+    assert_match("^-?%d+", "-5", 0..2, &[]);
+    assert_match("^-?%d+", "42", 0..2, &[]);
+    assert_match("^*", "*x", 0..1, &[]);
+    assert_match("^+", "+", 0..1, &[]);
+    assert_match("^+?x", "x", 0..1, &[]);
+    assert_no_match("a^b", "ab");
+    assert_no_match("[%d%s]", "d");
+}
+
 #[test]
 fn test_greedy_quantifiers_engine() {
     assert_match("a*", "aaa", 0..3, &[]);
@@ -152,16 +170,15 @@ fn test_captures_simple_engine() {
 
 #[test]
 fn test_captures_quantified_engine() {
-    assert_match("(a)*", "aaa", 0..3, &[Some(2..3)]);
-    assert_match("(a)+", "aaa", 0..3, &[Some(2..3)]);
-    assert_match("(a)?", "a", 0..1, &[Some(0..1)]);
-    assert_match("(a)?", "", 0..0, &[None]);
-    assert_match("a(b)*c", "abbbc", 0..5, &[Some(3..4)]);
-    assert_match("a(b)+c", "abbbc", 0..5, &[Some(3..4)]);
-    assert_match("a(b)?c", "abc", 0..3, &[Some(1..2)]);
-    assert_match("a(b)?c", "ac", 0..2, &[None]);
-    assert_match("a(b)-c", "abbbc", 0..5, &[Some(3..4)]);
-    assert_match("a(b)-c", "abbbc", 0..5, &[Some(3..4)]);
+    // A capture is not a single-byte item: a quantifier after it is a literal
+    assert_no_match("(a)*", "aaa");
+    assert_match("(a)*", "aa*", 1..3, &[Some(1..2)]);
+    assert_match("(a)+", "a+", 0..2, &[Some(0..1)]);
+    assert_no_match("(x)?", "x");
+    assert_match("a(b)?c", "ab?c", 0..4, &[Some(1..2)]);
+    assert_no_match("a(b)?c", "ac");
+    assert_match("a(b)-c", "ab-c", 0..4, &[Some(1..2)]);
+    assert_match("(a*)*", "aa*", 0..3, &[Some(0..2)]);
 }
 
 #[test]
@@ -170,6 +187,29 @@ fn test_captures_nested_engine() {
     assert_match("((.)%w*)", "a1 b2", 0..2, &[Some(0..2), Some(0..1)]);
 }
 
+#[test]
+fn test_back_reference_engine() {
+    assert_match("(.)%1", "abccd", 2..4, &[Some(2..3)]);
+    assert_match(
+        "([\"'])([^\"']*)%1",
+        "say 'hi' now",
+        4..8,
+        &[Some(4..5), Some(5..7)],
+    );
+    assert_match("(a*)b%1", "aabaa", 0..5, &[Some(0..2)]);
+    assert_no_match("()%1x", "x");
+    assert_no_match("(.)%1", "abcd");
+    assert_no_match("([\"'])([^\"']*)%1", "'hi\"");
+}
+
+#[test]
+fn test_invalid_back_reference_engine() {
+    let kind = |pattern| find(pattern, "aa").map_err(|err| err.kind());
+    assert_eq!(kind("%1"), Err(ErrorKind::InvalidCaptureIndex(1)));
+    assert_eq!(kind("(a%1)"), Err(ErrorKind::InvalidCaptureIndex(1)));
+    assert_eq!(kind("(a)%2"), Err(ErrorKind::InvalidCaptureIndex(2)));
+}
+
 #[test]
 fn test_balanced_engine() {
     assert_match("%b()", "(inner)", 0..7, &[]);
@@ -190,19 +230,57 @@ fn test_frontier_engine() {
     assert_no_match("%f[%s]a", " a");
 
     assert_match("%f[a]a", "a", 0..1, &[]);
-    assert_match("%f[^a]b", "b", 0..1, &[]);
+    assert_no_match("%f[^a]b", "b");
+    assert_match("%f[^a]b", "ab", 1..2, &[]);
+}
+
+#[test]
+fn test_frontier_string_boundaries_engine() {
+    assert_match("%f[^%w]", "hello", 5..5, &[]);
+    assert_match("%w+%f[^%w]", "hello", 0..5, &[]);
+    assert_match("%f[%w]%w+", "hello", 0..5, &[]);
+    assert_match("%f[\0]", "abc", 3..3, &[]);
+    assert_no_match("%f[^\0]", "");
+    assert_no_match("%f[%W]", "");
 }
 
 #[test]
 fn test_backtracking_engine() {
     assert_no_match("a*b", "aaac");
     assert_no_match("a+b", "aaac");
-    assert_match("(ab)+a", "abab", 0..3, &[]);
+    assert_match("(ab)a", "abab", 0..3, &[Some(0..2)]);
     assert_match("(a*)b", "aaab", 0..4, &[Some(0..3)]);
     assert_match("(a+)b", "aaab", 0..4, &[Some(0..3)]);
     assert_match("a[bc]+d", "abbcd", 0..5, &[]);
 }
 
+#[test]
+fn test_backtracking_into_captures_engine() {
+    assert_match("(a*)ab", "aaab", 0..4, &[Some(0..2)]);
+    assert_match("(a+)a", "aaa", 0..3, &[Some(0..2)]);
+    assert_match("(.-)b", "aaab", 0..4, &[Some(0..3)]);
+    assert_match("((a*)a)b", "aaab", 0..4, &[Some(0..3), Some(0..2)]);
+    assert_match(
+        "([\"'])(.-)%1",
+        "say 'hi' now",
+        4..8,
+        &[Some(4..5), Some(5..7)],
+    );
+    assert_match(
+        "(%w+)=(.-);",
+        "k=v1;k2=v2;",
+        0..5,
+        &[Some(0..1), Some(2..4)],
+    );
+    assert_match(
+        "^(.-)%s*=%s*(.-)%s*$",
+        "key  =  value  ",
+        0..15,
+        &[Some(0..3), Some(8..13)],
+    );
+    assert_no_match("(a*)ab", "aaa");
+}
+
 #[test]
 fn test_empty_engine() {
     assert_match("", "", 0..0, &[]);
@@ -351,18 +429,18 @@ fn test_pattern_with_utf8_content_engine() {
 
 #[test]
 fn test_quantifiers_with_capturing_groups_engine() {
-    assert_match("(a)+", "aaa", 0..3, &[Some(2..3)]);
-    assert_match("(ab)+", "ababab", 0..6, &[Some(4..6)]);
-    assert_match("(a)*", "aaa", 0..3, &[Some(2..3)]);
-    assert_match("(a)*", "", 0..0, &[None]);
-    assert_match("(a)?", "a", 0..1, &[Some(0..1)]);
-    assert_match("(a)?", "", 0..0, &[None]);
-    assert_match("(a)-", "aaa", 0..0, &[None]);
+    assert_match("(a+)", "aaa", 0..3, &[Some(0..3)]);
+    assert_match("(a*)", "", 0..0, &[Some(0..0)]);
+    assert_match("(a?)", "a", 0..1, &[Some(0..1)]);
+    assert_match("(a-)", "aaa", 0..0, &[Some(0..0)]);
+    assert_no_match("(ab)+", "ababab");
+    assert_match("%b()*", "(a)*", 0..4, &[]);
+    assert_match("(a)%1*", "aa*", 0..3, &[Some(0..1)]);
 }
 
 #[test]
 fn test_edge_cases_and_backtracking_engine() {
-    assert_match("(a+)+", "aaa", 0..3, &[Some(0..3)]);
+    assert_match("(a+)a", "aaa", 0..3, &[Some(0..2)]);
     assert_match("[ab][cd]", "ac", 0..2, &[]);
     assert_match("[ab][cd]", "bd", 0..2, &[]);
     assert_no_match("[ab][cd]", "ab");
@@ -374,7 +452,7 @@ fn test_edge_cases_and_backtracking_engine() {
 #[test]
 fn test_real_world_patterns_advanced_engine() {
     let html = "<div class='item'><span>Product: </span>Laptop</div><div class='price'>$999</div>";
-    let pattern = "<div class='([^']+)'>([^<]*<span>[^<]*</span>)?([^<]*)</div>";
+    let pattern = "<div class='([^']+)'>([^<]*<span>[^<]*</span>)([^<]*)</div>";
 
     let result = find(pattern, html).unwrap().unwrap();
     let (full, captures) = result;
@@ -414,3 +492,70 @@ fn test_subsequent_captures_engine() {
         &[Some(0..3), Some(4..8), Some(9..12)],
     );
 }
+
+#[test]
+fn test_long_input_engine() {
+    let long = format!("{}x", "a".repeat(100_000));
+    assert_match(".-x", &long, 0..100_001, &[]);
+    assert_match("%a+", &long, 0..100_001, &[]);
+    assert_match("(a*)x$", &long, 0..100_001, &[Some(0..100_000)]);
+    assert_match("(a-)x", &long, 0..100_001, &[Some(0..100_000)]);
+
+    let nested = format!("{}{}", "(".repeat(50_000), ")".repeat(50_000));
+    assert_match("%b()", &nested, 0..100_000, &[]);
+    assert_no_match("^a-b", &long);
+}
+
+#[test]
+fn test_failing_search_over_long_input_engine() {
+    let long = "ab".repeat(100_000);
+    assert_no_match("(a)(b)%d", &long);
+    assert_no_match("b$a", &long);
+    assert_match(
+        "(a)(b)$",
+        &long,
+        199_998..200_000,
+        &[Some(199_998..199_999), Some(199_999..200_000)],
+    );
+}
+
+#[test]
+fn test_start_position_prefilter_engine() {
+    assert_match("(a*)b", "xxaab", 2..5, &[Some(2..4)]);
+    assert_match("a?b", "xxab", 2..4, &[]);
+    assert_match("a-b", "xxaab", 2..5, &[]);
+    assert_match("()%d+", "abc123", 3..6, &[Some(3..3)]);
+    assert_match("%f[%a]%a+", "12 word", 3..7, &[]);
+    assert_match("%b()", "x(y)", 1..4, &[]);
+    assert_match("[^a]", "aab", 2..3, &[]);
+    assert_match("x*$", "abc", 3..3, &[]);
+    assert_match("(a)%1", "xaa", 1..3, &[Some(1..2)]);
+    assert_no_match("(%d+)%.", "12 34 56");
+
+    let log = format!("{}ERROR: disk full", "INFO: ok\n".repeat(10_000));
+    assert_match("ERROR: (.*)", &log, 90_000..90_016, &[Some(90_007..90_016)]);
+}
+
+#[test]
+fn test_suffix_anchored_engine() {
+    let path = format!("{}/archive.tar.gz", "dir".repeat(50_000));
+    let len = path.len();
+    assert_match("%.(%w+)$", &path, len - 3..len, &[Some(len - 2..len)]);
+    assert_match(
+        "(%w+)%.(%w+)$",
+        &path,
+        len - 6..len,
+        &[Some(len - 6..len - 3), Some(len - 2..len)],
+    );
+    assert_match("[^/]*$", &path, len - 14..len, &[]);
+    assert_no_match("%.txt$", &path);
+    assert_no_match("%d+$", &path);
+
+    assert_match("a?b$", "xab", 1..3, &[]);
+    assert_match("(a-)b$", "aab", 0..3, &[Some(0..2)]);
+    assert_match("x.-y$", "xaxby", 0..5, &[]);
+    assert_match("%b()$", "(a)(b)", 3..6, &[]);
+    assert_match("()$", "abc", 3..3, &[Some(3..3)]);
+    assert_match("^ab$", "ab", 0..2, &[]);
+    assert_no_match("b$", "");
+}